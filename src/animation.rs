@@ -0,0 +1,218 @@
+use bevy::{log, prelude::*, sprite::TextureAtlas, utils::HashMap};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    config::{self, Section},
+    daytime::GameSpeed,
+    workers::MovingWorker,
+};
+
+const ANIMATIONS_PATH: &str = "worker_anim.cfg";
+const STILL_TEXTURE: &str = "worker.png";
+// chance per second for a standing worker to start fidgeting
+const FIDGET_CHANCE: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AnimationKind {
+    Idle,
+    Walk,
+    Fidget,
+    Cheer,
+}
+
+const ANIMATION_KINDS: [AnimationKind; 4] = [
+    AnimationKind::Idle,
+    AnimationKind::Walk,
+    AnimationKind::Fidget,
+    AnimationKind::Cheer,
+];
+
+impl AnimationKind {
+    fn key(self) -> &'static str {
+        match self {
+            AnimationKind::Idle => "idle",
+            AnimationKind::Walk => "walk",
+            AnimationKind::Fidget => "fidget",
+            AnimationKind::Cheer => "cheer",
+        }
+    }
+}
+
+struct Animation {
+    frames: Vec<u32>,
+    fps: f32,
+    looping: bool,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frames: vec![1],
+            fps: 1.,
+            looping: true,
+        }
+    }
+}
+
+impl Animation {
+    fn from_section(section: &Section) -> Self {
+        let mut animation = Animation::default();
+        if let Some(frames) = section.get("frames") {
+            let frames: Result<Vec<u32>, _> = frames.split_whitespace().map(str::parse).collect();
+            match frames {
+                Ok(frames) if !frames.is_empty() => animation.frames = frames,
+                _ => log::warn!("Invalid frames in [{}]", section.name),
+            }
+        }
+        if let Some(fps) = section.get_parsed("fps") {
+            animation.fps = fps;
+        }
+        if let Some(looping) = section.get_parsed("looping") {
+            animation.looping = looping;
+        }
+        animation
+    }
+}
+
+pub struct WorkerSprites {
+    pub atlas: Handle<TextureAtlas>,
+    pub head: u32,
+    animations: HashMap<AnimationKind, Animation>,
+}
+
+impl WorkerSprites {
+    pub fn idle_frame(&self) -> u32 {
+        self.animations[&AnimationKind::Idle].frames[0]
+    }
+}
+
+impl FromWorld for WorkerSprites {
+    fn from_world(world: &mut World) -> Self {
+        let sections = config::load_asset(ANIMATIONS_PATH).unwrap_or_else(|| {
+            log::warn!("Cannot find {}, workers stand still", ANIMATIONS_PATH);
+            // the still sprite has a head and a body frame, every animation shows the body
+            let mut section = Section::default();
+            section.set("texture", STILL_TEXTURE.to_string());
+            section.set("columns", "2".to_string());
+            vec![section]
+        });
+        let atlas_section = &sections[0];
+        let texture = atlas_section.get("texture").unwrap_or("worker_anim.png");
+        let columns = atlas_section.get_parsed("columns").unwrap_or(10);
+        let size = atlas_section
+            .get("size")
+            .and_then(|s| {
+                let mut parts = s.split_whitespace().map(str::parse::<f32>);
+                Some(Vec2::new(parts.next()?.ok()?, parts.next()?.ok()?))
+            })
+            .unwrap_or_else(|| Vec2::new(25., 24.75));
+        let head = atlas_section.get_parsed("head").unwrap_or(0);
+
+        let mut animations = HashMap::default();
+        for kind in ANIMATION_KINDS.iter() {
+            let animation = sections
+                .iter()
+                .find(|s| s.name == kind.key())
+                .map(Animation::from_section)
+                .unwrap_or_default();
+            animations.insert(*kind, animation);
+        }
+
+        let asset_server = world.get_resource_mut::<AssetServer>().unwrap();
+        let tex_handle = asset_server.load(texture);
+        let atlas = TextureAtlas::from_grid(tex_handle, size, columns, 1);
+        let mut atlases = world.get_resource_mut::<Assets<TextureAtlas>>().unwrap();
+        WorkerSprites {
+            atlas: atlases.add(atlas),
+            head,
+            animations,
+        }
+    }
+}
+
+pub struct WorkerBody;
+// Served workers stay in place until the cheer is over
+pub struct Cheering;
+
+pub struct WorkerAnimation {
+    kind: AnimationKind,
+    elapsed: f32,
+}
+
+impl Default for WorkerAnimation {
+    fn default() -> Self {
+        Self {
+            kind: AnimationKind::Idle,
+            elapsed: 0.,
+        }
+    }
+}
+
+impl WorkerAnimation {
+    fn play(&mut self, kind: AnimationKind) {
+        if self.kind != kind {
+            self.kind = kind;
+            self.elapsed = 0.;
+        }
+    }
+}
+
+fn animate_workers(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<GameSpeed>,
+    sprites: Res<WorkerSprites>,
+    mut workers: Query<(
+        Entity,
+        &mut WorkerAnimation,
+        &Children,
+        Option<&MovingWorker>,
+        Option<&Cheering>,
+    )>,
+    mut bodies: Query<&mut TextureAtlasSprite, With<WorkerBody>>,
+) {
+    let dt = time.delta_seconds() * speed.multiplier() as f32;
+    let mut rng = thread_rng();
+    for (entity, mut animation, children, moving, cheering) in workers.iter_mut() {
+        if cheering.is_some() {
+            animation.play(AnimationKind::Cheer);
+        } else if moving.is_some() {
+            animation.play(AnimationKind::Walk);
+        } else if animation.kind != AnimationKind::Fidget {
+            animation.play(AnimationKind::Idle);
+            if rng.gen_bool((FIDGET_CHANCE * dt as f64).min(1.)) {
+                animation.play(AnimationKind::Fidget);
+            }
+        }
+        animation.elapsed += dt;
+
+        let current = &sprites.animations[&animation.kind];
+        let len = current.frames.len();
+        let mut frame = (animation.elapsed * current.fps) as usize;
+        if current.looping {
+            frame %= len;
+        } else if frame >= len {
+            frame = len - 1;
+            match animation.kind {
+                AnimationKind::Cheer => {
+                    commands.entity(entity).remove::<Cheering>();
+                }
+                AnimationKind::Fidget => animation.play(AnimationKind::Idle),
+                _ => {}
+            }
+        }
+        for child in children.iter() {
+            if let Ok(mut sprite) = bodies.get_mut(*child) {
+                sprite.index = current.frames[frame];
+            }
+        }
+    }
+}
+
+pub struct AnimationPlugin;
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<WorkerSprites>()
+            .add_system(animate_workers.system());
+    }
+}
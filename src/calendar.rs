@@ -0,0 +1,134 @@
+use bevy::{log, prelude::*};
+
+use crate::{
+    config::{self, parse_floats, Section},
+    daytime::{Daytime, Weekday},
+};
+
+const CALENDAR_PATH: &str = "calendar.cfg";
+const DEFAULT_WEEKEND_DEMAND: f32 = 0.2;
+
+enum Recurrence {
+    Weekly(Weekday),
+    // a single day of the run, counted from 1
+    Once(u32),
+}
+
+struct SpecialDay {
+    name: String,
+    recurrence: Recurrence,
+    from_hour: u32,
+    to_hour: u32,
+    demand: f32,
+}
+
+impl SpecialDay {
+    fn from_section(section: &Section) -> Option<Self> {
+        let recurrence = if let Some(day) = section.get_parsed("day") {
+            Recurrence::Once(day)
+        } else if let Some(weekday) = section.get("weekday") {
+            let weekday = Weekday::from_name(weekday);
+            if weekday.is_none() {
+                log::warn!("Unknown weekday in [{}]", section.name);
+            }
+            Recurrence::Weekly(weekday?)
+        } else {
+            log::warn!("Skipping [{}] without day or weekday", section.name);
+            return None;
+        };
+        let (from_hour, to_hour) = match section.get("hours").map(parse_floats).as_deref() {
+            Some(&[from, to]) => (from as u32, to as u32),
+            Some(_) => {
+                log::warn!("Invalid hours in [{}]", section.name);
+                (0, 24)
+            }
+            None => (0, 24),
+        };
+        Some(SpecialDay {
+            name: section.name.clone(),
+            recurrence,
+            from_hour,
+            to_hour,
+            demand: section.get_parsed("demand").unwrap_or(1.),
+        })
+    }
+
+    fn is_today(&self, daytime: &Daytime) -> bool {
+        match self.recurrence {
+            Recurrence::Weekly(weekday) => daytime.weekday() == weekday,
+            Recurrence::Once(day) => daytime.day() == day,
+        }
+    }
+
+    fn is_active(&self, daytime: &Daytime) -> bool {
+        let hour = daytime.get_hours();
+        self.is_today(daytime) && hour >= self.from_hour && hour < self.to_hour
+    }
+}
+
+pub struct Calendar {
+    weekend_demand: f32,
+    special_days: Vec<SpecialDay>,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        let sections = config::load_asset(CALENDAR_PATH).unwrap_or_else(|| {
+            log::warn!("Cannot find {}, using defaults", CALENDAR_PATH);
+            vec![]
+        });
+        let weekend_demand = sections
+            .iter()
+            .filter(|s| s.name.is_empty())
+            .find_map(|s| s.get_parsed("weekend_demand"))
+            .unwrap_or(DEFAULT_WEEKEND_DEMAND);
+        let special_days = sections
+            .iter()
+            .filter(|s| !s.name.is_empty())
+            .filter_map(SpecialDay::from_section)
+            .collect();
+        Self {
+            weekend_demand,
+            special_days,
+        }
+    }
+}
+
+impl Calendar {
+    /// Multiplier for office spawn chances at the current time
+    pub fn demand(&self, daytime: &Daytime) -> f32 {
+        let weekend = if daytime.weekday().is_weekend() {
+            self.weekend_demand
+        } else {
+            1.
+        };
+        self.special_days
+            .iter()
+            .filter(|s| s.is_active(daytime))
+            .fold(weekend, |demand, s| demand * s.demand)
+    }
+
+    /// Names of the special days falling on the current day
+    pub fn today(&self, daytime: &Daytime) -> Vec<&str> {
+        self.special_days
+            .iter()
+            .filter(|s| s.is_today(daytime))
+            .map(|s| s.name.as_str())
+            .collect()
+    }
+
+    /// Name of the special day affecting demand right now
+    pub fn active(&self, daytime: &Daytime) -> Option<&str> {
+        self.special_days
+            .iter()
+            .find(|s| s.is_active(daytime))
+            .map(|s| s.name.as_str())
+    }
+}
+
+pub struct CalendarPlugin;
+impl Plugin for CalendarPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Calendar>();
+    }
+}
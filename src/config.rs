@@ -0,0 +1,181 @@
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bevy::{log, prelude::Color};
+
+// Plain text configs: `key = value` lines grouped under optional `[section]` headers.
+// Anything after `#` is a comment.
+#[derive(Debug, Default)]
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+impl Section {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: vec![],
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        let value = self.get(key)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            log::warn!("Invalid value for `{}` in [{}]: {}", key, self.name, value);
+        }
+        parsed
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        self.entries.push((key.to_string(), value));
+    }
+}
+
+pub fn parse_floats(value: &str) -> Vec<f32> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse().ok())
+        .filter(|x: &f32| x.is_finite())
+        .collect()
+}
+
+/// `r, g, b` or `r, g, b, a` in 0..1
+pub fn parse_color(value: &str) -> Option<Color> {
+    match parse_floats(value)[..] {
+        [r, g, b] => Some(Color::rgb(r, g, b)),
+        [r, g, b, a] => Some(Color::rgba(r, g, b, a)),
+        _ => None,
+    }
+}
+
+pub fn parse(source: &str) -> Vec<Section> {
+    let mut sections = vec![Section::default()];
+    for (n, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section::new(name.trim()));
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let section = sections.last_mut().unwrap();
+            section
+                .entries
+                .push((key.trim().to_string(), value.trim().to_string()));
+        } else {
+            log::warn!("Skipping malformed config line {}: {}", n + 1, line);
+        }
+    }
+    sections
+}
+
+pub fn load(path: &str) -> Option<Vec<Section>> {
+    match fs::read_to_string(path) {
+        Ok(source) => Some(parse(&source)),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => {
+            log::warn!("Cannot read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Loads a file shipped in `assets/`, looked up from the same root as the asset server
+pub fn load_asset(name: &str) -> Option<Vec<Section>> {
+    let root = env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| env::current_exe().ok()?.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    load(&root.join("assets").join(name).to_string_lossy())
+}
+
+pub fn save(path: &str, sections: &[Section]) {
+    let mut out = String::new();
+    for section in sections {
+        if !section.name.is_empty() {
+            out.push_str(&format!("[{}]\n", section.name));
+        }
+        for (key, value) in section.entries.iter() {
+            out.push_str(&format!("{} = {}\n", key, value));
+        }
+        out.push('\n');
+    }
+    if let Err(e) = fs::write(path, out) {
+        log::error!("Cannot write {}: {}", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sections_and_skips_comments() {
+        let sections = parse(
+            "top = 1\n\
+             # a comment\n\
+             [keys]\n\
+             place = Left  # trailing comment\n\
+             broken line\n\
+             [ pad ]\n\
+             speed=2.5\n",
+        );
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].name, "");
+        assert_eq!(sections[0].get("top"), Some("1"));
+        assert_eq!(sections[1].name, "keys");
+        assert_eq!(sections[1].get("place"), Some("Left"));
+        assert_eq!(sections[1].entries.len(), 1);
+        assert_eq!(sections[2].name, "pad");
+        assert_eq!(sections[2].get_parsed::<f32>("speed"), Some(2.5));
+    }
+
+    #[test]
+    fn invalid_values_are_none() {
+        let mut section = Section::new("test");
+        section.set("count", "many".to_string());
+        assert_eq!(section.get_parsed::<u32>("count"), None);
+        assert_eq!(section.get_parsed::<u32>("missing"), None);
+    }
+
+    #[test]
+    fn floats_skip_garbage_and_non_finite_values() {
+        assert_eq!(parse_floats("1, 2.5  -3"), vec![1., 2.5, -3.]);
+        assert_eq!(parse_floats("1, x, inf, NaN, 4"), vec![1., 4.]);
+        assert!(parse_floats("").is_empty());
+    }
+
+    #[test]
+    fn colors_need_three_or_four_components() {
+        assert_eq!(parse_color("1, 0, 0"), Some(Color::rgb(1., 0., 0.)));
+        assert_eq!(
+            parse_color("1, 0, 0, 0.5"),
+            Some(Color::rgba(1., 0., 0., 0.5))
+        );
+        assert_eq!(parse_color("1, 0"), None);
+        assert_eq!(parse_color("1, 0, 0, 0, 0"), None);
+    }
+
+    #[test]
+    fn first_entry_wins() {
+        let sections = parse("[a]\nkey = 1\nkey = 2\n");
+        assert_eq!(sections[1].get("key"), Some("1"));
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::{log, prelude::*, sprite::SpriteResizeMode};
+use hex2d::Spacing;
+
+use crate::{
+    config::{self, parse_color},
+    daytime::Daytime,
+    field::{Map, State, SIZE},
+    MainCamera,
+};
+
+const DAYLIGHT_PATH: &str = "daylight.cfg";
+// keeps every palette colour distinguishable under the darkest tint, whatever the intensity
+const MAX_TINT_ALPHA: f32 = 0.5;
+const HOURS_IN_DAY: f32 = 24.;
+// above tiles and workers, with lights on top so they are not dimmed
+const TINT_Z: f32 = 998.;
+const GLOW_Z: f32 = 998.5;
+const WINDOW_Z: f32 = 998.6;
+const WINDOW_SIDE: f32 = 14.;
+const WINDOW_OFFSET: f32 = 14.;
+const GLOW_SIZE: f32 = 2.2 * SIZE;
+
+struct Phase {
+    hour: f32,
+    tint: Vec4,
+    lights: f32,
+}
+
+struct Daylight {
+    phases: Vec<Phase>,
+    intensity: f32,
+    lights: f32,
+    tint_material: Handle<ColorMaterial>,
+    window_material: Handle<ColorMaterial>,
+    glow_material: Handle<ColorMaterial>,
+}
+
+impl Daylight {
+    /// Tint and light strength at the given hour, blended between the surrounding phases
+    fn sample(&self, hour: f32) -> (Color, f32) {
+        let len = self.phases.len();
+        if len == 0 {
+            return (Color::NONE, 0.);
+        }
+        let next = self.phases.iter().position(|p| p.hour > hour).unwrap_or(0);
+        let prev = (next + len - 1) % len;
+        let (a, b) = (&self.phases[prev], &self.phases[next]);
+        let span = (b.hour - a.hour).rem_euclid(HOURS_IN_DAY);
+        let k = if span > 0. {
+            (hour - a.hour).rem_euclid(HOURS_IN_DAY) / span
+        } else {
+            0.
+        };
+        let tint = a.tint.lerp(b.tint, k);
+        let alpha = (tint.w * self.intensity).min(MAX_TINT_ALPHA);
+        let lights = (a.lights + (b.lights - a.lights) * k) * self.lights;
+        (Color::rgba(tint.x, tint.y, tint.z, alpha), lights)
+    }
+}
+
+impl FromWorld for Daylight {
+    fn from_world(world: &mut World) -> Self {
+        let sections = config::load_asset(DAYLIGHT_PATH).unwrap_or_else(|| {
+            log::warn!("Cannot find {}, day/night cycle is disabled", DAYLIGHT_PATH);
+            vec![]
+        });
+        let global = sections.iter().find(|s| s.name.is_empty());
+        let intensity = global
+            .and_then(|s| s.get_parsed("intensity"))
+            .unwrap_or(1_f32);
+        let lights = global.and_then(|s| s.get_parsed("lights")).unwrap_or(1_f32);
+        let mut phases: Vec<_> = sections
+            .iter()
+            .filter(|s| !s.name.is_empty())
+            .filter_map(|section| {
+                let hour = section.get_parsed::<f32>("hour").filter(|h| h.is_finite());
+                let color = section.get("color").and_then(parse_color);
+                if hour.is_none() || color.is_none() {
+                    log::warn!("Skipping [{}] without hour or color", section.name);
+                    return None;
+                }
+                let alpha = section.get_parsed("alpha").unwrap_or(0.);
+                let [r, g, b, _] = color?.as_rgba_f32();
+                Some(Phase {
+                    hour: hour?,
+                    tint: Vec4::new(r, g, b, alpha),
+                    lights: section.get_parsed("lights").unwrap_or(0.),
+                })
+            })
+            .collect();
+        phases.sort_by(|a, b| a.hour.partial_cmp(&b.hour).unwrap());
+
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        Daylight {
+            phases,
+            intensity,
+            lights,
+            tint_material: materials.add(Color::NONE.into()),
+            window_material: materials.add(Color::rgba(1., 0.9, 0.5, 0.).into()),
+            glow_material: materials.add(ColorMaterial::modulated_texture(
+                asset_server.load("glow.png"),
+                Color::rgba(1., 0.8, 0.4, 0.),
+            )),
+        }
+    }
+}
+
+struct DaylightTint;
+struct LightItem;
+
+fn setup(mut commands: Commands, daylight: Res<Daylight>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: daylight.tint_material.clone(),
+            transform: Transform::from_xyz(0., 0., TINT_Z),
+            ..Default::default()
+        })
+        .insert(DaylightTint);
+}
+
+fn follow_camera(
+    windows: Res<Windows>,
+    camera: Query<&Transform, (With<MainCamera>, Without<DaylightTint>)>,
+    mut tint: Query<(&mut Transform, &mut Sprite), With<DaylightTint>>,
+) {
+    let wnd = windows.get_primary().unwrap();
+    let camera = if let Ok(x) = camera.single() {
+        x
+    } else {
+        return;
+    };
+    for (mut transform, mut sprite) in tint.iter_mut() {
+        transform.translation = camera.translation.truncate().extend(TINT_Z);
+        transform.scale = camera.scale;
+        sprite.size = Vec2::new(wnd.width(), wnd.height());
+    }
+}
+
+fn update_colors(
+    daytime: Res<Daytime>,
+    daylight: Res<Daylight>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !daytime.is_changed() && !daylight.is_changed() {
+        return;
+    }
+    let (tint, lights) = daylight.sample(daytime.time_of_day());
+    if let Some(material) = materials.get_mut(&daylight.tint_material) {
+        material.color = tint;
+    }
+    if let Some(material) = materials.get_mut(&daylight.window_material) {
+        material.color.set_a(lights);
+    }
+    if let Some(material) = materials.get_mut(&daylight.glow_material) {
+        material.color.set_a(lights * 0.6);
+    }
+}
+
+fn draw_lights(
+    mut commands: Commands,
+    map: Res<Map>,
+    daylight: Res<Daylight>,
+    items: Query<Entity, With<LightItem>>,
+) {
+    if !map.is_changed() {
+        return;
+    }
+    for entity in items.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (c, state) in map.tiles() {
+        let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+        match state {
+            State::Active => {
+                for (dx, dy) in [(-1., -1.), (-1., 1.), (1., -1.), (1., 1.)].iter() {
+                    let (x, y) = (x + dx * WINDOW_OFFSET, y + dy * WINDOW_OFFSET);
+                    commands
+                        .spawn_bundle(SpriteBundle {
+                            sprite: Sprite::new(Vec2::splat(WINDOW_SIDE)),
+                            material: daylight.window_material.clone(),
+                            transform: Transform::from_xyz(x, y, WINDOW_Z),
+                            ..Default::default()
+                        })
+                        .insert(LightItem);
+                }
+            }
+            State::BreakShop => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            size: Vec2::splat(GLOW_SIZE),
+                            resize_mode: SpriteResizeMode::Manual,
+                            ..Default::default()
+                        },
+                        material: daylight.glow_material.clone(),
+                        transform: Transform::from_xyz(x, y, GLOW_Z),
+                        ..Default::default()
+                    })
+                    .insert(LightItem);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct DaylightPlugin;
+impl Plugin for DaylightPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Daylight>()
+            .add_startup_system(setup.system())
+            .add_system(follow_camera.system())
+            .add_system(update_colors.system())
+            .add_system(draw_lights.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn daylight(intensity: f32) -> Daylight {
+        Daylight {
+            phases: vec![
+                Phase {
+                    hour: 6.,
+                    tint: Vec4::new(0., 0., 0.2, 0.4),
+                    lights: 0.,
+                },
+                Phase {
+                    hour: 18.,
+                    tint: Vec4::new(0., 0., 0.4, 0.8),
+                    lights: 1.,
+                },
+            ],
+            intensity,
+            lights: 1.,
+            tint_material: Handle::default(),
+            window_material: Handle::default(),
+            glow_material: Handle::default(),
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn phase_hour_gives_its_own_tint() {
+        let (color, lights) = daylight(1.).sample(6.);
+        let [_, _, b, a] = color.as_rgba_f32();
+        assert_close(b, 0.2);
+        assert_close(a, 0.4);
+        assert_close(lights, 0.);
+    }
+
+    #[test]
+    fn blends_between_phases_across_midnight() {
+        let (color, lights) = daylight(1.).sample(0.);
+        let [_, _, b, _] = color.as_rgba_f32();
+        assert_close(b, 0.3);
+        assert_close(lights, 0.5);
+    }
+
+    #[test]
+    fn alpha_is_capped_after_intensity() {
+        let [.., a] = daylight(1.).sample(18.).0.as_rgba_f32();
+        assert_close(a, MAX_TINT_ALPHA);
+        let [.., a] = daylight(0.5).sample(18.).0.as_rgba_f32();
+        assert_close(a, 0.4);
+    }
+
+    #[test]
+    fn no_phases_means_no_tint() {
+        let mut daylight = daylight(1.);
+        daylight.phases.clear();
+        assert_eq!(daylight.sample(12.), (Color::NONE, 0.));
+    }
+}
@@ -2,14 +2,97 @@ use std::fmt::Display;
 
 use bevy::{core::FixedTimestep, prelude::*};
 
+use crate::input::{Action, ActionEvent};
+
 const MINUTES_IN_TICK: u32 = 1;
 const TICKS_IN_RUN: u32 = 1;
 const MAX_TICKS: u32 = MINUTES_IN_TICK * 60 * 24;
+pub const TICKS_IN_HOUR: u32 = MINUTES_IN_TICK * 60;
+const SPEEDS: [u32; 3] = [1, 2, 4];
 
 #[derive(Debug)]
 pub struct Daytime(u32, u32);
 
 pub struct TickEvent;
+pub struct NewHourEvent(pub u32);
+pub struct NewDayEvent(pub u32);
+
+// Day 1 of a run is a Monday
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+impl Weekday {
+    pub fn name(self) -> &'static str {
+        match self {
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        }
+    }
+
+    fn short(self) -> &'static str {
+        &self.name()[..3]
+    }
+
+    pub fn of_day(day: u32) -> Self {
+        WEEKDAYS[(day.max(1) - 1) as usize % WEEKDAYS.len()]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        WEEKDAYS
+            .iter()
+            .copied()
+            .find(|w| w.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_weekend(self) -> bool {
+        matches!(self, Weekday::Saturday | Weekday::Sunday)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GameSpeed {
+    // the clock stands still until a difficulty is chosen
+    pub started: bool,
+    pub paused: bool,
+    speed_idx: usize,
+}
+
+impl GameSpeed {
+    pub fn multiplier(&self) -> u32 {
+        if self.paused || !self.started {
+            0
+        } else {
+            SPEEDS[self.speed_idx]
+        }
+    }
+
+    fn speed_up(&mut self) {
+        self.speed_idx = (self.speed_idx + 1) % SPEEDS.len();
+    }
+}
 
 impl Default for Daytime {
     fn default() -> Self {
@@ -22,15 +105,33 @@ impl Daytime {
         self.1 % 60
     }
 
-    fn get_hours(&self) -> u32 {
+    pub fn day(&self) -> u32 {
+        self.0
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        Weekday::of_day(self.0)
+    }
+
+    pub fn get_hours(&self) -> u32 {
         self.1 / 60
     }
 
+    /// Ticks since the start of the run, stands still while paused
+    pub fn ticks(&self) -> u32 {
+        self.0 * MAX_TICKS + self.1
+    }
+
+    /// Hours since midnight, with minutes as the fraction
+    pub fn time_of_day(&self) -> f32 {
+        self.1 as f32 / 60.
+    }
+
     fn add(&mut self, ticks: u32) {
         self.1 += ticks;
         if self.1 >= MAX_TICKS {
             self.0 += self.1 / MAX_TICKS;
-            self.1 -= MAX_TICKS;
+            self.1 %= MAX_TICKS;
         }
     }
 }
@@ -39,7 +140,8 @@ impl Display for Daytime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Day {}, {:02}:{:02}",
+            "{}, day {}, {:02}:{:02}",
+            self.weekday().short(),
             self.0,
             self.get_hours(),
             self.get_minutes()
@@ -47,11 +149,39 @@ impl Display for Daytime {
     }
 }
 
-fn update_daytime(mut daytime: ResMut<Daytime>, mut events: EventWriter<TickEvent>) {
-    daytime.add(TICKS_IN_RUN);
-    for _ in 0..TICKS_IN_RUN {
+fn update_daytime(
+    mut daytime: ResMut<Daytime>,
+    speed: Res<GameSpeed>,
+    mut events: EventWriter<TickEvent>,
+    mut hour_events: EventWriter<NewHourEvent>,
+    mut day_events: EventWriter<NewDayEvent>,
+) {
+    let ticks = TICKS_IN_RUN * speed.multiplier();
+    if ticks == 0 {
+        return;
+    }
+    let hour = daytime.get_hours();
+    let day = daytime.0;
+    daytime.add(ticks);
+    for _ in 0..ticks {
         events.send(TickEvent);
     }
+    if daytime.get_hours() != hour {
+        hour_events.send(NewHourEvent(daytime.get_hours()));
+    }
+    if daytime.0 != day {
+        day_events.send(NewDayEvent(daytime.0));
+    }
+}
+
+fn change_speed(mut speed: ResMut<GameSpeed>, mut actions: EventReader<ActionEvent>) {
+    for ActionEvent(action) in actions.iter() {
+        match action {
+            Action::Pause => speed.paused = !speed.paused,
+            Action::SpeedUp => speed.speed_up(),
+            _ => {}
+        }
+    }
 }
 
 pub struct DaytimePlugin;
@@ -59,11 +189,44 @@ pub struct DaytimePlugin;
 impl Plugin for DaytimePlugin {
     fn build(&self, app: &mut bevy::prelude::AppBuilder) {
         app.init_resource::<Daytime>()
+            .init_resource::<GameSpeed>()
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::steps_per_second(8.))
                     .with_system(update_daytime.system()),
             )
-            .add_event::<TickEvent>();
+            .add_system(change_speed.system())
+            .add_event::<TickEvent>()
+            .add_event::<NewHourEvent>()
+            .add_event::<NewDayEvent>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_day_is_monday() {
+        assert_eq!(Weekday::of_day(1), Weekday::Monday);
+        assert_eq!(Weekday::of_day(0), Weekday::Monday);
+    }
+
+    #[test]
+    fn weeks_wrap_around() {
+        assert_eq!(Weekday::of_day(6), Weekday::Saturday);
+        assert_eq!(Weekday::of_day(7), Weekday::Sunday);
+        assert_eq!(Weekday::of_day(8), Weekday::Monday);
+        assert!(Weekday::of_day(14).is_weekend());
+        assert!(!Weekday::of_day(15).is_weekend());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for day in WEEKDAYS.iter() {
+            assert_eq!(Weekday::from_name(day.name()), Some(*day));
+        }
+        assert_eq!(Weekday::from_name("friday"), Some(Weekday::Friday));
+        assert_eq!(Weekday::from_name("Someday"), None);
     }
 }
@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+
+use crate::{
+    daytime::GameSpeed,
+    field::{NeighbourWeights, State, SHOP_PRICE},
+    ui::Money,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Brutal,
+}
+
+const DIFFICULTIES: [Difficulty; 4] = [
+    Difficulty::Easy,
+    Difficulty::Normal,
+    Difficulty::Hard,
+    Difficulty::Brutal,
+];
+
+// Chances for a new tile, indexed by the number of obstacles next to it
+const EASY_WEIGHTS: NeighbourWeights = [
+    [
+        (State::Inactive, 45),
+        (State::Active, 40),
+        (State::Obstacle, 15),
+    ],
+    [
+        (State::Inactive, 60),
+        (State::Active, 15),
+        (State::Obstacle, 25),
+    ],
+    [
+        (State::Inactive, 40),
+        (State::Active, 5),
+        (State::Obstacle, 55),
+    ],
+];
+const NORMAL_WEIGHTS: NeighbourWeights = [
+    [
+        (State::Inactive, 40),
+        (State::Active, 40),
+        (State::Obstacle, 20),
+    ],
+    [
+        (State::Inactive, 50),
+        (State::Active, 10),
+        (State::Obstacle, 40),
+    ],
+    [
+        (State::Inactive, 25),
+        (State::Active, 0),
+        (State::Obstacle, 75),
+    ],
+];
+const HARD_WEIGHTS: NeighbourWeights = [
+    [
+        (State::Inactive, 30),
+        (State::Active, 45),
+        (State::Obstacle, 25),
+    ],
+    [
+        (State::Inactive, 40),
+        (State::Active, 10),
+        (State::Obstacle, 50),
+    ],
+    [
+        (State::Inactive, 15),
+        (State::Active, 0),
+        (State::Obstacle, 85),
+    ],
+];
+const BRUTAL_WEIGHTS: NeighbourWeights = [
+    [
+        (State::Inactive, 25),
+        (State::Active, 45),
+        (State::Obstacle, 30),
+    ],
+    [
+        (State::Inactive, 30),
+        (State::Active, 10),
+        (State::Obstacle, 60),
+    ],
+    [
+        (State::Inactive, 10),
+        (State::Active, 0),
+        (State::Obstacle, 90),
+    ],
+];
+
+pub struct DifficultySettings {
+    pub difficulty: Difficulty,
+    // applied to every worker kind's patience from workers.cfg
+    pub patience_multiplier: f32,
+    pub fee_for_overwait: i32,
+    pub base_chance_to_spawn_worker: u32,
+    pub timer_multiplier: f32,
+    pub neighbours_weights: NeighbourWeights,
+    // at least one shop's worth, so the first placement does not wait for income
+    pub starting_money: u32,
+    // the n-th expansion adds n shop slots, but never more than this
+    pub max_shops_increase: u32,
+}
+
+impl Difficulty {
+    fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Brutal => "Brutal",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "patient workers, a head start and open land",
+            Difficulty::Normal => "the game as it was meant to be played",
+            Difficulty::Hard => "busy offices, impatient workers, dense city",
+            Difficulty::Brutal => "every mistake costs you",
+        }
+    }
+
+    pub fn settings(self) -> DifficultySettings {
+        match self {
+            Difficulty::Easy => DifficultySettings {
+                difficulty: self,
+                patience_multiplier: 1.5,
+                fee_for_overwait: -1,
+                base_chance_to_spawn_worker: 7,
+                timer_multiplier: 1.15,
+                neighbours_weights: EASY_WEIGHTS,
+                starting_money: 2 * SHOP_PRICE,
+                max_shops_increase: 4,
+            },
+            Difficulty::Normal => DifficultySettings {
+                difficulty: self,
+                patience_multiplier: 1.,
+                fee_for_overwait: -1,
+                base_chance_to_spawn_worker: 9,
+                timer_multiplier: 1.2,
+                neighbours_weights: NORMAL_WEIGHTS,
+                starting_money: SHOP_PRICE,
+                max_shops_increase: 3,
+            },
+            Difficulty::Hard => DifficultySettings {
+                difficulty: self,
+                patience_multiplier: 0.75,
+                fee_for_overwait: -2,
+                base_chance_to_spawn_worker: 11,
+                timer_multiplier: 1.25,
+                neighbours_weights: HARD_WEIGHTS,
+                starting_money: SHOP_PRICE,
+                max_shops_increase: 2,
+            },
+            Difficulty::Brutal => DifficultySettings {
+                difficulty: self,
+                patience_multiplier: 0.5,
+                fee_for_overwait: -3,
+                base_chance_to_spawn_worker: 13,
+                timer_multiplier: 1.3,
+                neighbours_weights: BRUTAL_WEIGHTS,
+                starting_money: SHOP_PRICE,
+                max_shops_increase: 1,
+            },
+        }
+    }
+}
+
+impl Default for DifficultySettings {
+    fn default() -> Self {
+        Difficulty::Normal.settings()
+    }
+}
+
+struct DifficultyMenu;
+struct DifficultyButton(Difficulty);
+
+struct DifficultyMaterials {
+    card: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+}
+
+impl FromWorld for DifficultyMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        DifficultyMaterials {
+            card: materials.add(Color::rgb_u8(230, 245, 255).into()),
+            button: materials.add(Color::rgb_u8(200, 220, 240).into()),
+            hovered: materials.add(Color::rgb_u8(255, 220, 150).into()),
+        }
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    materials: Res<DifficultyMaterials>,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    let title = TextStyle {
+        font: font.clone(),
+        font_size: 36.0,
+        color: Color::BLACK,
+    };
+    let style = TextStyle {
+        font,
+        font_size: 24.0,
+        color: Color::BLACK,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(30.),
+                    top: Val::Px(150.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.)),
+                align_items: AlignItems::Stretch,
+                ..Default::default()
+            },
+            material: materials.card.clone(),
+            ..Default::default()
+        })
+        .insert(DifficultyMenu)
+        .with_children(|ec| {
+            ec.spawn_bundle(TextBundle {
+                text: Text::with_section("Choose difficulty", title, Default::default()),
+                ..Default::default()
+            });
+            for difficulty in DIFFICULTIES.iter() {
+                let label = format!("{}: {}", difficulty.label(), difficulty.description());
+                ec.spawn_bundle(ButtonBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(4.)),
+                        padding: Rect::all(Val::Px(6.)),
+                        ..Default::default()
+                    },
+                    material: materials.button.clone(),
+                    ..Default::default()
+                })
+                .insert(DifficultyButton(*difficulty))
+                .with_children(|ec| {
+                    ec.spawn_bundle(TextBundle {
+                        text: Text::with_section(label, style.clone(), Default::default()),
+                        ..Default::default()
+                    });
+                });
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn choose_difficulty(
+    mut commands: Commands,
+    materials: Res<DifficultyMaterials>,
+    mut settings: ResMut<DifficultySettings>,
+    mut money: ResMut<Money>,
+    mut speed: ResMut<GameSpeed>,
+    mut buttons: Query<
+        (&Interaction, &DifficultyButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+    menus: Query<Entity, With<DifficultyMenu>>,
+) {
+    for (interaction, button, mut material) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                *settings = button.0.settings();
+                // an untouched balance keeps low money warnings quiet at the start
+                if money.0 != settings.starting_money {
+                    money.0 = settings.starting_money;
+                }
+                speed.started = true;
+                for entity in menus.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.button.clone(),
+        }
+    }
+}
+
+pub struct DifficultyPlugin;
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DifficultySettings>()
+            .init_resource::<DifficultyMaterials>()
+            .add_startup_system(setup.system())
+            .add_system(choose_difficulty.system());
+    }
+}
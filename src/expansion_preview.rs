@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use hex2d::Spacing;
+
+use crate::{
+    field::{build_hex_shape, Expansion, ExpansionCandidates, State, SIZE},
+    palette::TileStyle,
+};
+
+struct ExpansionGhost;
+struct ExpansionCard;
+struct ExpansionChoice(usize);
+
+struct ExpansionMaterials {
+    card: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    chosen: Handle<ColorMaterial>,
+}
+
+impl FromWorld for ExpansionMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        ExpansionMaterials {
+            card: materials.add(Color::rgb_u8(230, 245, 255).into()),
+            button: materials.add(Color::rgb_u8(200, 220, 240).into()),
+            chosen: materials.add(Color::rgb_u8(255, 220, 150).into()),
+        }
+    }
+}
+
+fn expansion_summary(expansion: &Expansion) -> String {
+    let count = |state| expansion.tiles.iter().filter(|(_, s)| *s == state).count();
+    let mut sector = expansion.sector.to_string();
+    sector[..1].make_ascii_uppercase();
+    format!(
+        "{}: {} offices, {} free, {} obstacles",
+        sector,
+        count(State::Active),
+        count(State::Inactive),
+        count(State::Obstacle)
+    )
+}
+
+fn draw_ghosts(
+    mut commands: Commands,
+    candidates: Res<ExpansionCandidates>,
+    style: Res<TileStyle>,
+    ghosts: Query<Entity, With<ExpansionGhost>>,
+) {
+    if !candidates.is_changed() && !style.is_changed() {
+        return;
+    }
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (n, expansion) in candidates.expansions.iter().enumerate() {
+        let (alpha, width) = if n == candidates.chosen {
+            (0.6, 6.0)
+        } else {
+            (0.2, 3.0)
+        };
+        for (c, state) in expansion.tiles.iter() {
+            let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+            let mut color = style.palette.color(*state);
+            color.set_a(alpha);
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &build_hex_shape(),
+                    ShapeColors::new(color),
+                    DrawMode::Stroke(StrokeOptions::default().with_line_width(width)),
+                    Transform::from_xyz(x, y, 0.05),
+                ))
+                .insert(ExpansionGhost);
+        }
+    }
+}
+
+fn draw_choices(
+    mut commands: Commands,
+    candidates: Res<ExpansionCandidates>,
+    materials: Res<ExpansionMaterials>,
+    asset_server: Res<AssetServer>,
+    cards: Query<Entity, With<ExpansionCard>>,
+) {
+    if !candidates.is_changed() {
+        return;
+    }
+    for entity in cards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let style = TextStyle {
+        font: asset_server.load("FiraSans-Bold.ttf"),
+        font_size: 24.0,
+        color: Color::BLACK,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(35.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.)),
+                align_items: AlignItems::Stretch,
+                ..Default::default()
+            },
+            material: materials.card.clone(),
+            ..Default::default()
+        })
+        .insert(ExpansionCard)
+        .with_children(|ec| {
+            ec.spawn_bundle(TextBundle {
+                text: Text::with_section("Next expansion", style.clone(), Default::default()),
+                ..Default::default()
+            });
+            for (n, expansion) in candidates.expansions.iter().enumerate() {
+                let material = if n == candidates.chosen {
+                    materials.chosen.clone()
+                } else {
+                    materials.button.clone()
+                };
+                ec.spawn_bundle(ButtonBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(4.)),
+                        padding: Rect::all(Val::Px(6.)),
+                        ..Default::default()
+                    },
+                    material,
+                    ..Default::default()
+                })
+                .insert(ExpansionChoice(n))
+                .with_children(|ec| {
+                    ec.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            expansion_summary(expansion),
+                            style.clone(),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
+            }
+        });
+}
+
+fn choose_layout(
+    mut candidates: ResMut<ExpansionCandidates>,
+    choices: Query<(&Interaction, &ExpansionChoice), Changed<Interaction>>,
+) {
+    for (interaction, choice) in choices.iter() {
+        if *interaction == Interaction::Clicked && candidates.chosen != choice.0 {
+            candidates.chosen = choice.0;
+            candidates.picked = candidates.expansions.get(choice.0).map(|e| e.sector);
+        }
+    }
+}
+
+pub struct ExpansionPreviewPlugin;
+impl Plugin for ExpansionPreviewPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ExpansionMaterials>()
+            .add_system(draw_ghosts.system())
+            .add_system(draw_choices.system())
+            .add_system(choose_layout.system());
+    }
+}
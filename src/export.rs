@@ -0,0 +1,393 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{log, prelude::*, utils::HashMap};
+use hex2d::{Coordinate, Spacing};
+
+use crate::{
+    config,
+    field::{Map, State, SIZE},
+    input::{Action, ActionEvent},
+    palette::TileStyle,
+    workers::{WaitingWorker, Worker},
+};
+
+const EXPORT_DIR: &str = "exports";
+const SETTINGS_PATH: &str = "settings.cfg";
+const EXPORT_SECTION: &str = "export";
+// PNG pixels per world unit, a tile is 50 pixels across
+const PNG_SCALE: f32 = 0.25;
+const OUTLINE: f32 = 10.;
+const WORKER_RADIUS: f32 = 8.;
+const QUEUE_BAR_WIDTH: f32 = 80.;
+const QUEUE_BAR_HEIGHT: f32 = 12.;
+const QUEUE_BAR_FULL: usize = 10;
+
+/// What gets drawn besides the tiles, read from the `[export]` section of the user settings
+struct ExportOptions {
+    workers: bool,
+    png: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        let mut options = ExportOptions {
+            workers: true,
+            png: true,
+        };
+        let sections = config::load(SETTINGS_PATH).unwrap_or_default();
+        if let Some(section) = sections.iter().find(|s| s.name == EXPORT_SECTION) {
+            if let Some(x) = section.get_parsed("workers") {
+                options.workers = x;
+            }
+            if let Some(x) = section.get_parsed("png") {
+                options.png = x;
+            }
+        }
+        options
+    }
+}
+
+/// Everything needed to draw the field, so rendering works without a window or GPU
+pub struct Snapshot {
+    tiles: Vec<(Coordinate, Color)>,
+    queues: HashMap<Coordinate, usize>,
+    workers: Vec<Vec2>,
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Snapshot {
+    pub fn new(map: &Map, style: &TileStyle) -> Self {
+        let (min, max) = map.bounds();
+        Self {
+            tiles: map
+                .tiles()
+                .map(|(c, state)| (c, style.palette.color(state)))
+                .collect(),
+            queues: map
+                .tiles()
+                .filter(|(_, state)| *state == State::BreakShop)
+                .map(|(c, _)| (c, 0))
+                .collect(),
+            workers: vec![],
+            min,
+            max,
+        }
+    }
+}
+
+fn center(c: Coordinate) -> Vec2 {
+    let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+    Vec2::new(x, y)
+}
+
+fn hex_corners(c: Coordinate) -> Vec<Vec2> {
+    let center = center(c);
+    (0..6)
+        .map(|n| {
+            let angle = (60. * n as f32).to_radians();
+            center + Vec2::new(angle.cos(), angle.sin()) * SIZE
+        })
+        .collect()
+}
+
+fn svg_color(color: Color) -> String {
+    let [r, g, b, _] = color.as_rgba_f32();
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        (r * 255.) as u8,
+        (g * 255.) as u8,
+        (b * 255.) as u8
+    )
+}
+
+pub fn render_svg(snapshot: &Snapshot) -> String {
+    let size = snapshot.max - snapshot.min;
+    // SVG grows downwards, the world grows upwards
+    let flip = |p: Vec2| Vec2::new(p.x - snapshot.min.x, snapshot.max.y - p.y);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {:.0} {:.0}\">\n",
+        size.x, size.y
+    );
+    for (c, color) in snapshot.tiles.iter() {
+        let points: Vec<_> = hex_corners(*c)
+            .into_iter()
+            .map(|p| {
+                let p = flip(p);
+                format!("{:.1},{:.1}", p.x, p.y)
+            })
+            .collect();
+        out.push_str(&format!(
+            "  <polygon points=\"{}\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"{}\"/>\n",
+            points.join(" "),
+            svg_color(*color),
+            OUTLINE
+        ));
+    }
+    for (c, queue) in snapshot.queues.iter() {
+        let p = flip(center(*c));
+        out.push_str(&format!(
+            "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"40\" text-anchor=\"middle\" \
+             dominant-baseline=\"middle\" fill=\"#ffffff\">{}</text>\n",
+            p.x, p.y, queue
+        ));
+    }
+    for worker in snapshot.workers.iter() {
+        let p = flip(*worker);
+        out.push_str(&format!(
+            "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"#ffffff\" stroke=\"#000000\"/>\n",
+            p.x, p.y, WORKER_RADIUS
+        ));
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// Distance from the hex center in units of the apothem, 1 on the edges
+fn hex_distance(offset: Vec2) -> f32 {
+    let apothem = SIZE * 3_f32.sqrt() / 2.;
+    [90_f32, 30., -30.]
+        .iter()
+        .map(|angle| {
+            let normal = Vec2::new(angle.to_radians().cos(), angle.to_radians().sin());
+            offset.dot(normal).abs()
+        })
+        .fold(0., f32::max)
+        / apothem
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let [r, g, b, a] = color.as_rgba_f32();
+    [
+        (r * 255.) as u8,
+        (g * 255.) as u8,
+        (b * 255.) as u8,
+        (a * 255.) as u8,
+    ]
+}
+
+pub fn render_png(snapshot: &Snapshot) -> Vec<u8> {
+    let size = (snapshot.max - snapshot.min) * PNG_SCALE;
+    let (width, height) = (size.x.ceil() as u32, size.y.ceil() as u32);
+    let colors: HashMap<_, _> = snapshot.tiles.iter().copied().collect();
+    let outline = 1. - OUTLINE / (SIZE * 3_f32.sqrt() / 2.);
+    let mut pixels = vec![0_u8; (width * height * 4) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let world = Vec2::new(
+                snapshot.min.x + (x as f32 + 0.5) / PNG_SCALE,
+                snapshot.max.y - (y as f32 + 0.5) / PNG_SCALE,
+            );
+            let c = Coordinate::from_pixel(world.x, world.y, Spacing::FlatTop(SIZE));
+            let offset = world - center(c);
+            let mut color = match colors.get(&c) {
+                Some(_) if hex_distance(offset) > outline => Color::BLACK,
+                Some(color) => *color,
+                None => continue,
+            };
+            if let Some(queue) = snapshot.queues.get(&c) {
+                let filled =
+                    QUEUE_BAR_WIDTH * (*queue).min(QUEUE_BAR_FULL) as f32 / QUEUE_BAR_FULL as f32;
+                let bar = offset + Vec2::new(QUEUE_BAR_WIDTH / 2., QUEUE_BAR_HEIGHT / 2.);
+                if bar.x >= 0. && bar.x < filled && bar.y >= 0. && bar.y < QUEUE_BAR_HEIGHT {
+                    color = Color::WHITE;
+                }
+            }
+            let i = ((y * width + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&rgba(color));
+        }
+    }
+    let radius = (WORKER_RADIUS * PNG_SCALE).max(1.);
+    for worker in snapshot.workers.iter() {
+        let cx = (worker.x - snapshot.min.x) * PNG_SCALE;
+        let cy = (snapshot.max.y - worker.y) * PNG_SCALE;
+        let (x0, x1) = (
+            (cx - radius).max(0.) as u32,
+            ((cx + radius).ceil() as u32).min(width),
+        );
+        let (y0, y1) = (
+            (cy - radius).max(0.) as u32,
+            ((cy + radius).ceil() as u32).min(height),
+        );
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let d = Vec2::new(x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                if d.length() < radius {
+                    let i = ((y * width + x) * 4) as usize;
+                    pixels[i..i + 4].copy_from_slice(&rgba(Color::WHITE));
+                }
+            }
+        }
+    }
+    encode_png(width, height, &pixels)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// zlib stream made of stored deflate blocks, larger but needs no compressor
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<_> = data.chunks(MAX_BLOCK).collect();
+    if blocks.is_empty() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    for (n, block) in blocks.iter().enumerate() {
+        let last = (n + 1 == blocks.len()) as u8;
+        let len = block.len() as u16;
+        out.push(last);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// 8-bit RGBA PNG from tightly packed rows of pixels
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // bit depth, RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks((width * 4).max(1) as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut out, b"IHDR", &header);
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Where the map was exported, without the extension
+pub struct MapExportedEvent(pub String);
+
+fn export_map(
+    mut actions: EventReader<ActionEvent>,
+    map: Res<Map>,
+    style: Res<TileStyle>,
+    options: Res<ExportOptions>,
+    workers: Query<(&Worker, &Transform, Option<&WaitingWorker>)>,
+    mut exported: EventWriter<MapExportedEvent>,
+) {
+    if !actions.iter().any(|ActionEvent(a)| *a == Action::Export) {
+        return;
+    }
+    let mut snapshot = Snapshot::new(&map, &style);
+    for (worker, transform, waiting) in workers.iter() {
+        if waiting.is_some() {
+            if let Some(queue) = snapshot.queues.get_mut(&worker.coffee) {
+                *queue += 1;
+            }
+        }
+        if options.workers {
+            snapshot.workers.push(transform.translation.truncate());
+        }
+    }
+    if let Err(e) = fs::create_dir_all(EXPORT_DIR) {
+        log::error!("Cannot create {}: {}", EXPORT_DIR, e);
+        return;
+    }
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let path = format!("{}/map-{}", EXPORT_DIR, stamp);
+    if let Err(e) = fs::write(format!("{}.svg", path), render_svg(&snapshot)) {
+        log::error!("Cannot write {}.svg: {}", path, e);
+        return;
+    }
+    if options.png {
+        if let Err(e) = fs::write(format!("{}.png", path), render_png(&snapshot)) {
+            log::error!("Cannot write {}.png: {}", path, e);
+        }
+    }
+    exported.send(MapExportedEvent(path));
+}
+
+pub struct ExportPlugin;
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ExportOptions>()
+            .add_event::<MapExportedEvent>()
+            .add_system(export_map.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn iend_chunk_has_known_crc() {
+        let mut out = vec![];
+        png_chunk(&mut out, b"IEND", &[]);
+        assert_eq!(
+            out,
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn png_has_signature_header_and_end() {
+        let png = encode_png(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(
+            &png[png.len() - 8..],
+            &[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn stored_blocks_split_large_data() {
+        let data = vec![7; 70_000];
+        let stream = zlib_stored(&data);
+        // header, two block headers, data and adler32
+        assert_eq!(stream.len(), 2 + 5 * 2 + data.len() + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + 65535], 1);
+    }
+}
@@ -1,10 +1,12 @@
+use std::collections::VecDeque;
+
 use bevy::{
     log,
     prelude::*,
     utils::{HashMap, HashSet},
 };
 use bevy_prototype_lyon::prelude::*;
-use hex2d::{Coordinate, Direction, Spacing, Spin};
+use hex2d::{Coordinate, Spacing};
 use hex2d_dpcext::algo::bfs::Traverser;
 use rand::{
     distributions::WeightedIndex,
@@ -13,44 +15,80 @@ use rand::{
 };
 
 use crate::{
-    daytime::TickEvent,
-    ui::{ChangeMoneyEvent, GeneratedNextRing, UpgradeTileEvent},
-    upgrade_particles::StartUpgradeEmitter,
-    workers::{ReturningWorker, SpawnWorkerEvent, WaitingWorker, Worker},
+    animation::Cheering,
+    calendar::Calendar,
+    daytime::{Daytime, GameSpeed, NewHourEvent, TickEvent},
+    difficulty::DifficultySettings,
+    input::{Action, ActionEvent},
+    palette::{spawn_pattern, TilePattern, TileStyle},
+    particles::{self, StartEmitter},
+    popups::MoneyPopupEvent,
+    reputation::Reputation,
+    ui::{ChangeMoneyEvent, MapExpandedEvent, Money, MoneyCategory, UpgradeTileEvent},
+    weather::CurrentWeather,
+    workers::{serve_order, ReturningWorker, ServedEvent, SpawnWorkerEvent, WaitingWorker, Worker},
     MainCamera,
 };
 
-const NEIGHBOURS_WEIGHTS: [[(State, u8); 3]; 3] = [
-    [
-        (State::Inactive, 40),
-        (State::Active, 40),
-        (State::Obstacle, 20),
-    ],
-    [
-        (State::Inactive, 50),
-        (State::Active, 10),
-        (State::Obstacle, 40),
-    ],
-    [
-        (State::Inactive, 25),
-        (State::Active, 0),
-        (State::Obstacle, 75),
-    ],
-];
+// Chances for a new tile, indexed by the number of obstacles next to it
+pub type NeighbourWeights = [[(State, u8); 3]; 3];
 pub const SIZE: f32 = 100.;
 
-pub const START_RING_TIMER_SECS: f32 = 10.;
-pub const TIMER_MULTIPLER: f32 = 1.2;
+pub const START_EXPANSION_TIMER_SECS: f32 = 10.;
+const SECTORS: [&str; 6] = [
+    "north-east",
+    "north",
+    "north-west",
+    "south-west",
+    "south",
+    "south-east",
+];
 
-const BASE_CHANCE_TO_SPAWN_WORKER: u32 = 9;
 const CHANCE_INCREASE_PER_TICK: u32 = 1;
 const HUNDRED_PERCENT_CHANCE: u32 = 200;
 
-const WAIT_TICKS_AFTER_SERVING: u32 = 3;
-const MAX_SHOPS_INCREASE: u32 = 3;
+pub const WAIT_TICKS_AFTER_SERVING: u32 = 3;
+const SERVICE_BAR_WIDTH: f32 = 80.;
+const SERVICE_BAR_HEIGHT: f32 = 8.;
+const SERVICE_BAR_BOTTOM: f32 = 62.;
 const STARTING_MAX_SHOPS: u32 = 1;
 
-#[derive(Debug, Clone, Copy)]
+pub const SHOP_PRICE: u32 = 5;
+const SHOP_UPKEEP_PER_HOUR: u32 = 1;
+
+const MAX_UNDO_STEPS: usize = 5;
+// 30 seconds at normal speed
+const UNDO_WINDOW_TICKS: u32 = 240;
+
+/// Multiplier for office spawn chances right now
+pub fn demand(
+    reputation: &Reputation,
+    calendar: &Calendar,
+    daytime: &Daytime,
+    weather: &CurrentWeather,
+) -> f32 {
+    reputation.multiplier() * calendar.demand(daytime) * weather.0.demand()
+}
+
+pub fn expected_ticks_between_workers(base_chance: u32, demand: f32) -> f32 {
+    if demand <= 0. {
+        return f32::INFINITY;
+    }
+    // E[T] = sum over k of P(no worker during the first k ticks)
+    let mut expected = 0.;
+    let mut no_worker_yet = 1.;
+    let mut ticks_wo_worker = 0.;
+    while no_worker_yet > 0. {
+        expected += no_worker_yet;
+        let chance = base_chance as f32 + CHANCE_INCREASE_PER_TICK as f32 * ticks_wo_worker;
+        let chance = (chance * demand / HUNDRED_PERCENT_CHANCE as f32).min(1.);
+        no_worker_yet *= 1. - chance;
+        ticks_wo_worker += demand;
+    }
+    expected
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum State {
     Inactive,
     Active,
@@ -59,15 +97,6 @@ pub enum State {
 }
 
 impl State {
-    fn color(&self) -> Color {
-        match self {
-            State::Inactive => Color::DARK_GRAY,
-            State::Active => Color::rgb_u8(82, 151, 255),
-            State::BreakShop => Color::rgb_u8(0, 112, 74),
-            State::Obstacle => Color::BLACK,
-        }
-    }
-
     fn is_obstacle(&self) -> bool {
         // matches!(self, State::Obstacle | State::Active)
         matches!(self, State::Obstacle)
@@ -81,26 +110,31 @@ impl State {
         matches!(self, State::BreakShop)
     }
 
-    fn is_upgradeable(&self) -> bool {
+    pub fn is_upgradeable(&self) -> bool {
         matches!(self, State::Inactive)
     }
 }
 
-pub struct NextRingTimer(pub Timer);
+pub struct ExpansionTimer(pub Timer);
 
-impl Default for NextRingTimer {
+impl Default for ExpansionTimer {
     fn default() -> Self {
-        Self(Timer::from_seconds(START_RING_TIMER_SECS as f32, false))
+        Self(Timer::from_seconds(
+            START_EXPANSION_TIMER_SECS as f32,
+            false,
+        ))
     }
 }
 
 struct SelectableTile;
 struct OfficeTile {
-    ticks_wo_worker: u32,
+    // weighted by demand, so quiet hours do not build up a rush for later
+    ticks_wo_worker: f32,
 }
-struct CoffeeTile {
+pub struct CoffeeTile {
     waiting_ticks: u32,
 }
+struct ServiceBar;
 
 struct GeneratedRings(i32);
 
@@ -119,10 +153,84 @@ impl Default for GeneratedRings {
 
 pub struct Map {
     tiles: HashMap<Coordinate, State>,
-    pub generated_rings: u32,
+    // exempt from upkeep
+    pub starting_shop: Coordinate,
+    pub expansions: u32,
+    // impassable until the weather clears
+    pub snowed: HashSet<Coordinate>,
+}
+
+impl Map {
+    pub fn get(&self, c: Coordinate) -> Option<State> {
+        self.tiles.get(&c).copied()
+    }
+
+    pub fn is_coffee(&self, c: Coordinate) -> bool {
+        self.get(c).map(|s| s.is_coffee()).unwrap_or(false)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (Coordinate, State)> + '_ {
+        self.tiles.iter().map(|(c, s)| (*c, *s))
+    }
+
+    /// Free coordinates next to the map, where it can grow
+    fn frontier(&self) -> HashSet<Coordinate> {
+        self.tiles
+            .keys()
+            .flat_map(|c| c.neighbors().to_vec())
+            .filter(|c| !self.tiles.contains_key(c))
+            .collect()
+    }
+
+    /// Bounding box of all tiles in world coordinates
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let half = Vec2::new(SIZE, SIZE * 3_f32.sqrt() / 2.);
+        let mut min = -half;
+        let mut max = half;
+        for c in self.tiles.keys() {
+            let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+            min = min.min(Vec2::new(x, y) - half);
+            max = max.max(Vec2::new(x, y) + half);
+        }
+        (min, max)
+    }
+
+    pub fn offices(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        self.tiles
+            .iter()
+            .filter(|(_, s)| matches!(s, State::Active))
+            .map(|(c, _)| *c)
+    }
+
+    /// Shortest path to the nearest tile satisfying `is_dest`, stored backwards
+    /// like `Worker::path`: the destination comes first and `from` is not included.
+    pub fn find_path(
+        &self,
+        from: Coordinate,
+        is_dest: impl Fn(Coordinate) -> bool,
+    ) -> Option<Vec<Coordinate>> {
+        if is_dest(from) {
+            return Some(vec![]);
+        }
+        let is_passable =
+            |c| !self.snowed.contains(&c) && self.get(c).map(|s| s.is_passable()).unwrap_or(false);
+        let mut traverser = Traverser::new(is_passable, is_dest, from);
+        let dest = traverser.find()?;
+        let mut path = vec![dest];
+        let mut end = dest;
+        loop {
+            let next = traverser.backtrace(end).unwrap();
+            if next == from {
+                break;
+            }
+            path.push(next);
+            end = next;
+        }
+        Some(path)
+    }
 }
 
-fn build_hex_shape() -> shapes::RegularPolygon {
+pub fn build_hex_shape() -> shapes::RegularPolygon {
     shapes::RegularPolygon {
         sides: 6,
         feature: shapes::RegularPolygonFeature::Radius(100.0),
@@ -130,83 +238,154 @@ fn build_hex_shape() -> shapes::RegularPolygon {
     }
 }
 
-fn spawn_tile(commands: &mut Commands, c: Coordinate, tile: State) -> Entity {
+fn tile_shape(c: Coordinate, tile: State, style: &TileStyle) -> ShapeBundle {
     let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+    GeometryBuilder::build_as(
+        &build_hex_shape(),
+        ShapeColors::outlined(style.palette.color(tile), Color::BLACK),
+        DrawMode::Outlined {
+            fill_options: FillOptions::default(),
+            outline_options: StrokeOptions::default().with_line_width(10.0),
+        },
+        Transform::from_xyz(x, y, 0.),
+    )
+}
+
+fn spawn_tile(commands: &mut Commands, c: Coordinate, tile: State, style: &TileStyle) -> Entity {
     let mut builder = commands.spawn();
     let builder = builder
-        .insert_bundle(GeometryBuilder::build_as(
-            &build_hex_shape(),
-            ShapeColors::outlined(tile.color(), Color::BLACK),
-            DrawMode::Outlined {
-                fill_options: FillOptions::default(),
-                outline_options: StrokeOptions::default().with_line_width(10.0),
-            },
-            Transform::from_xyz(x, y, 0.),
-        ))
+        .insert_bundle(tile_shape(c, tile, style))
         .insert(c)
-        .insert(SelectableTile);
+        .insert(SelectableTile)
+        .with_children(|ec| spawn_pattern(ec, tile, style));
     match tile {
         State::Active => {
-            builder.insert(OfficeTile { ticks_wo_worker: 0 });
+            builder.insert(OfficeTile {
+                ticks_wo_worker: 0.,
+            });
         }
         State::BreakShop => {
-            builder.insert(CoffeeTile { waiting_ticks: 0 });
+            builder
+                .insert(CoffeeTile { waiting_ticks: 0 })
+                .with_children(|ec| {
+                    let bar = shapes::Rectangle {
+                        width: SERVICE_BAR_WIDTH,
+                        height: SERVICE_BAR_HEIGHT,
+                        origin: shapes::RectangleOrigin::BottomLeft,
+                    };
+                    let (left, bottom) = (-SERVICE_BAR_WIDTH / 2., SERVICE_BAR_BOTTOM);
+                    ec.spawn_bundle(GeometryBuilder::build_as(
+                        &bar,
+                        ShapeColors::new(Color::rgba(0., 0., 0., 0.4)),
+                        DrawMode::Fill(FillOptions::default()),
+                        Transform::from_xyz(left, bottom, 0.3),
+                    ));
+                    ec.spawn_bundle(GeometryBuilder::build_as(
+                        &bar,
+                        ShapeColors::new(Color::rgb_u8(255, 220, 120)),
+                        DrawMode::Fill(FillOptions::default()),
+                        Transform::from_xyz(left, bottom, 0.35),
+                    ))
+                    .insert(ServiceBar);
+                });
         }
         _ => {}
     }
     builder.id()
 }
 
+// Rebuilds tiles in place, so offices and shops keep their counters
+fn repaint_tiles(
+    mut commands: Commands,
+    style: Res<TileStyle>,
+    map: Res<Map>,
+    tiles: Query<(Entity, &Coordinate), With<SelectableTile>>,
+    patterns: Query<Entity, With<TilePattern>>,
+) {
+    if !style.is_changed() {
+        return;
+    }
+    for entity in patterns.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (entity, c) in tiles.iter() {
+        let tile = if let Some(x) = map.get(*c) {
+            x
+        } else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert_bundle(tile_shape(*c, tile, &style))
+            .with_children(|ec| spawn_pattern(ec, tile, &style));
+    }
+}
+
+// Shows the service cooldown left before the shop can serve the next worker
+fn update_service_bars(
+    shops: Query<(&CoffeeTile, &Children), Changed<CoffeeTile>>,
+    mut bars: Query<&mut Transform, With<ServiceBar>>,
+) {
+    for (shop, children) in shops.iter() {
+        let ratio = shop.waiting_ticks as f32 / WAIT_TICKS_AFTER_SERVING as f32;
+        for child in children.iter() {
+            if let Ok(mut transform) = bars.get_mut(*child) {
+                transform.scale.x = ratio;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn office_system(
     mut query: Query<(&Coordinate, &mut OfficeTile)>,
     mut events: EventReader<TickEvent>,
     mut spawn_events: EventWriter<SpawnWorkerEvent>,
     map: Res<Map>,
+    difficulty: Res<DifficultySettings>,
+    reputation: Res<Reputation>,
+    calendar: Res<Calendar>,
+    daytime: Res<Daytime>,
+    weather: Res<CurrentWeather>,
 ) {
     let mut rng = thread_rng();
+    let demand = demand(&reputation, &calendar, &daytime, &weather);
     for _ in events.iter() {
         for (coord, mut office) in query.iter_mut() {
-            let chance =
-                BASE_CHANCE_TO_SPAWN_WORKER + CHANCE_INCREASE_PER_TICK * office.ticks_wo_worker;
+            let chance = difficulty.base_chance_to_spawn_worker as f32
+                + CHANCE_INCREASE_PER_TICK as f32 * office.ticks_wo_worker;
+            let chance = (chance * demand) as u32;
             let next = rng.gen_range(0..HUNDRED_PERCENT_CHANCE);
             if next < chance {
-                office.ticks_wo_worker = 0;
+                office.ticks_wo_worker = 0.;
                 // spawn worker
-                let is_passable = |c| map.tiles.get(&c).map(State::is_passable).unwrap_or(false);
-                let is_dest = |c| map.tiles.get(&c).map(State::is_coffee).unwrap_or(false);
-                let mut traverser = Traverser::new(is_passable, is_dest, *coord);
-                let coffee = if let Some(x) = traverser.find() {
+                let path = if let Some(x) = map.find_path(*coord, |c| map.is_coffee(c)) {
                     x
                 } else {
                     log::debug!("Cannot find nearest coffee shop");
                     continue;
                 };
-                let mut path = vec![coffee];
-                let mut end = coffee;
-                loop {
-                    let next = traverser.backtrace(end).unwrap();
-                    if next == *coord {
-                        break;
-                    }
-                    path.push(next);
-                    end = next;
-                }
+                let coffee = path[0];
                 log::debug!("Spawn worker from {:?} to {:?}", coord, coffee);
                 let event = SpawnWorkerEvent(*coord, coffee, path);
                 spawn_events.send(event);
             } else {
-                office.ticks_wo_worker += 1;
+                office.ticks_wo_worker += demand;
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_coffees(
     mut commands: Commands,
     w_workers: Query<(Entity, &Worker, &WaitingWorker), Without<ReturningWorker>>,
     mut shops: Query<(&Coordinate, &mut CoffeeTile)>,
     mut ticks: EventReader<TickEvent>,
     mut money: EventWriter<ChangeMoneyEvent>,
+    mut popups: EventWriter<MoneyPopupEvent>,
+    mut emitter_events: EventWriter<StartEmitter>,
+    mut served: EventWriter<ServedEvent>,
 ) {
     let mut set = HashSet::with_capacity_and_hasher(2, Default::default());
     for _ in ticks.iter() {
@@ -216,10 +395,13 @@ fn process_coffees(
                 continue;
             }
             log::debug!("looking for workers");
-            let res = w_workers.iter().find(|(entity, w, ww)| {
-                !ww.is_dead() && w.coffee == *coord && !set.contains(entity)
-            });
-            let (w_entity, worker, _) = if let Some(x) = res {
+            let res = w_workers
+                .iter()
+                .filter(|(entity, w, ww)| {
+                    !ww.is_dead(w) && w.coffee == *coord && !set.contains(entity)
+                })
+                .min_by_key(|(_, w, ww)| serve_order(w, ww));
+            let (w_entity, worker, waiting) = if let Some(x) = res {
                 x
             } else {
                 log::debug!("no workers");
@@ -228,8 +410,38 @@ fn process_coffees(
             set.insert(w_entity);
             shop.waiting_ticks = WAIT_TICKS_AFTER_SERVING;
             let mut ec = commands.entity(w_entity);
-            ec.insert(ReturningWorker);
-            money.send(ChangeMoneyEvent(worker.will_bring_money as i32));
+            ec.insert(ReturningWorker).insert(Cheering);
+            let earned = worker.will_bring_money as i32;
+            money.send(ChangeMoneyEvent(earned, MoneyCategory::Service));
+            popups.send(MoneyPopupEvent(*coord, earned));
+            let patience_left = waiting.patience_left(worker) as f32 / worker.patience as f32;
+            served.send(ServedEvent(*coord, patience_left));
+            let (x, y) = coord.to_pixel(Spacing::FlatTop(SIZE));
+            emitter_events.send(StartEmitter(particles::COIN, Vec3::new(x, y, 0.3)));
+        }
+    }
+}
+
+fn charge_upkeep(
+    map: Res<Map>,
+    shop_tiles: Query<&Coordinate, With<CoffeeTile>>,
+    mut hours: EventReader<NewHourEvent>,
+    mut money: EventWriter<ChangeMoneyEvent>,
+    mut popups: EventWriter<MoneyPopupEvent>,
+) {
+    for _ in hours.iter() {
+        // the shop the run starts with is free to keep
+        let paying: Vec<_> = shop_tiles
+            .iter()
+            .filter(|c| **c != map.starting_shop)
+            .collect();
+        if paying.is_empty() {
+            continue;
+        }
+        let upkeep = paying.len() as u32 * SHOP_UPKEEP_PER_HOUR;
+        money.send(ChangeMoneyEvent(-(upkeep as i32), MoneyCategory::Upkeep));
+        for c in paying {
+            popups.send(MoneyPopupEvent(*c, -(SHOP_UPKEEP_PER_HOUR as i32)));
         }
     }
 }
@@ -240,25 +452,13 @@ fn return_worker(
     map: Res<Map>,
 ) {
     for (entity, mut worker) in query.iter_mut() {
-        let is_passable = |c| map.tiles.get(&c).map(State::is_passable).unwrap_or(false);
-        let is_dest = |c| c == worker.home;
-        let mut traverser = Traverser::new(is_passable, is_dest, worker.coffee);
-        let coffee = if let Some(x) = traverser.find() {
+        let home = worker.home;
+        let path = if let Some(x) = map.find_path(worker.coffee, |c| c == home) {
             x
         } else {
-            log::debug!("Cannot find nearest coffee shop");
+            log::debug!("Cannot find a way home");
             continue;
         };
-        let mut path = vec![coffee];
-        let mut end = coffee;
-        loop {
-            let next = traverser.backtrace(end).unwrap();
-            if next == worker.coffee {
-                break;
-            }
-            path.push(next);
-            end = next;
-        }
         worker.path = path;
         worker.waited_for_coffee = true;
         commands
@@ -268,49 +468,146 @@ fn return_worker(
     }
 }
 
-fn setup(mut commands: Commands, map: Res<Map>) {
+fn setup(mut commands: Commands, map: Res<Map>, style: Res<TileStyle>) {
     for (c, tile) in map.tiles.iter() {
-        spawn_tile(&mut commands, *c, *tile);
+        spawn_tile(&mut commands, *c, *tile, &style);
     }
 }
 
-fn generate_next_ring(
-    mut commands: Commands,
-    mut map: ResMut<Map>,
-    mut timer: ResMut<NextRingTimer>,
-    time: Res<Time>,
-    mut next_ring_event: EventWriter<GeneratedNextRing>,
-    mut shops: ResMut<CoffeeShops>,
+// Candidates are rolled with the weights of the chosen difficulty
+fn reroll_candidates(
+    map: Res<Map>,
+    difficulty: Res<DifficultySettings>,
+    mut candidates: ResMut<ExpansionCandidates>,
 ) {
-    if !timer.0.tick(time.delta()).finished() {
-        return;
+    if difficulty.is_changed() {
+        let picked = candidates.picked;
+        *candidates = ExpansionCandidates::roll(&map, &difficulty.neighbours_weights, picked);
     }
-    let duration = timer.0.duration().mul_f32(TIMER_MULTIPLER);
-    timer.0 = Timer::new(duration, false);
-    map.generated_rings += 1;
-    let next_ring =
-        Coordinate::new(0, 0).ring_iter(map.generated_rings as i32, Spin::CW(Direction::XY));
+}
+
+fn roll_tiles(
+    map: &Map,
+    coordinates: impl Iterator<Item = Coordinate>,
+    weights: &NeighbourWeights,
+    rng: &mut impl Rng,
+) -> Vec<(Coordinate, State)> {
     let mut next_tiles = vec![];
-    for c in next_ring {
+    for c in coordinates {
         let obstacles = c
             .neighbors()
             .iter()
             .filter_map(|c| map.tiles.get(c))
             .filter(|tile| tile.is_obstacle())
-            .count();
-        let weights = NEIGHBOURS_WEIGHTS[obstacles];
+            .count()
+            .min(weights.len() - 1);
+        let weights = weights[obstacles];
         let distr = WeightedIndex::new(weights.iter().map(|item| item.1)).unwrap();
-        let mut rng = rand::thread_rng();
-        let tile = weights[distr.sample(&mut rng)].0;
+        let tile = weights[distr.sample(rng)].0;
         next_tiles.push((c, tile));
     }
-    for (c, tile) in next_tiles {
-        map.tiles.insert(c, tile);
-        spawn_tile(&mut commands, c, tile);
+    next_tiles
+}
+
+fn sector_of(c: Coordinate) -> usize {
+    let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+    let angle = y.atan2(x).to_degrees().rem_euclid(360.);
+    (angle / 60.) as usize % SECTORS.len()
+}
+
+/// Frontier tiles of one sector, rolled ahead so the player can see and pick them
+pub struct Expansion {
+    pub sector: &'static str,
+    pub tiles: Vec<(Coordinate, State)>,
+}
+
+#[derive(Default)]
+pub struct ExpansionCandidates {
+    pub expansions: Vec<Expansion>,
+    pub chosen: usize,
+    // sector the player picked, kept over rerolls while it has room to grow
+    pub picked: Option<&'static str>,
+}
+
+impl ExpansionCandidates {
+    fn roll(map: &Map, weights: &NeighbourWeights, picked: Option<&'static str>) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut sectors = vec![vec![]; SECTORS.len()];
+        for c in map.frontier() {
+            sectors[sector_of(c)].push(c);
+        }
+        let expansions = sectors
+            .into_iter()
+            .enumerate()
+            .filter(|(_, coordinates)| !coordinates.is_empty())
+            .map(|(n, coordinates)| Expansion {
+                sector: SECTORS[n],
+                tiles: roll_tiles(map, coordinates.into_iter(), weights, &mut rng),
+            })
+            .collect::<Vec<_>>();
+        // without a pick the city grows in a random direction
+        let chosen = picked
+            .and_then(|p| expansions.iter().position(|e| e.sector == p))
+            .unwrap_or_else(|| rng.gen_range(0..expansions.len().max(1)));
+        Self {
+            expansions,
+            chosen,
+            picked,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn expand_map(
+    mut commands: Commands,
+    mut map: ResMut<Map>,
+    mut timer: ResMut<ExpansionTimer>,
+    mut candidates: ResMut<ExpansionCandidates>,
+    time: Res<Time>,
+    speed: Res<GameSpeed>,
+    mut expanded_events: EventWriter<MapExpandedEvent>,
+    mut shops: ResMut<CoffeeShops>,
+    difficulty: Res<DifficultySettings>,
+    style: Res<TileStyle>,
+) {
+    if !timer.0.tick(time.delta() * speed.multiplier()).finished() {
+        return;
     }
-    next_ring_event.send(GeneratedNextRing(map.generated_rings));
-    let delta = (map.generated_rings - 1).min(MAX_SHOPS_INCREASE);
-    shops.1 += delta;
+    let chosen = candidates.chosen;
+    let expansion = if let Some(x) = candidates.expansions.get(chosen) {
+        x
+    } else {
+        return;
+    };
+    map.expansions += 1;
+    let sectors = SECTORS.len() as u32;
+    let ring_done = map.expansions % sectors == 0;
+    let mut duration = timer.0.duration();
+    if ring_done {
+        duration = duration.mul_f32(difficulty.timer_multiplier);
+    }
+    timer.0 = Timer::new(duration, false);
+    let mean = expansion
+        .tiles
+        .iter()
+        .map(|(c, _)| {
+            let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+            Vec2::new(x, y)
+        })
+        .fold(Vec2::ZERO, |sum, p| sum + p)
+        / expansion.tiles.len().max(1) as f32;
+    let center = Coordinate::from_pixel(mean.x, mean.y, Spacing::FlatTop(SIZE));
+    for (c, tile) in expansion.tiles.iter() {
+        map.tiles.insert(*c, *tile);
+        spawn_tile(&mut commands, *c, *tile, &style);
+    }
+    // the cap grows once a full ring of sectors is in, as it did with whole rings
+    if ring_done {
+        shops.1 += (map.expansions / sectors).min(difficulty.max_shops_increase);
+    }
+    expanded_events.send(MapExpandedEvent(expansion.sector, center));
+    let picked = candidates.picked;
+    *candidates = ExpansionCandidates::roll(&map, &difficulty.neighbours_weights, picked);
 }
 
 impl Default for Map {
@@ -325,22 +622,38 @@ impl Default for Map {
 
         let mut rng = rand::thread_rng();
         let mut chosen = neigh.choose_multiple(&mut rng, 3);
-        tiles.insert(*chosen.next().unwrap(), State::BreakShop);
+        let starting_shop = *chosen.next().unwrap();
+        tiles.insert(starting_shop, State::BreakShop);
         for n in chosen {
             tiles.insert(*n, State::Obstacle);
         }
         Self {
             tiles,
-            generated_rings: 1,
+            starting_shop,
+            expansions: 0,
+            snowed: HashSet::default(),
         }
     }
 }
 
+struct Placement {
+    coordinate: Coordinate,
+    previous: State,
+    placed_at: u32,
+}
+
+#[derive(Default)]
+struct PlacementHistory(VecDeque<Placement>);
+
+/// Placements made so far can no longer be undone, e.g. one of them earned a reward
+pub struct LockPlacementsEvent;
+
 pub struct SelectedHex {
     entity: Entity,
-    coordinate: Coordinate,
+    pub coordinate: Coordinate,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn upgrade_hex(
     mut commands: Commands,
     selected: Res<Option<SelectedHex>>,
@@ -348,23 +661,33 @@ fn upgrade_hex(
     mut events: EventReader<UpgradeTileEvent>,
     tiles: Query<(Entity, &Coordinate), With<SelectableTile>>,
     mut shops: ResMut<CoffeeShops>,
-    mut emitter_events: EventWriter<StartUpgradeEmitter>,
+    mut emitter_events: EventWriter<StartEmitter>,
+    mut history: ResMut<PlacementHistory>,
+    daytime: Res<Daytime>,
+    wallet: Res<Money>,
+    mut money: EventWriter<ChangeMoneyEvent>,
+    mut popups: EventWriter<MoneyPopupEvent>,
+    style: Res<TileStyle>,
 ) {
     for _ in events.iter() {
         if shops.0 >= shops.1 {
             continue;
         }
+        if wallet.0 < SHOP_PRICE {
+            log::debug!("Not enough money for a shop");
+            continue;
+        }
         let selected = if let Some(x) = selected.as_ref() {
             x
         } else {
             continue;
         };
-        let upgradable = map
-            .tiles
-            .get(&selected.coordinate)
-            .map(|s| s.is_upgradeable())
-            .unwrap_or(false);
-        if !upgradable {
+        let previous = if let Some(x) = map.get(selected.coordinate) {
+            x
+        } else {
+            continue;
+        };
+        if !previous.is_upgradeable() || map.snowed.contains(&selected.coordinate) {
             continue;
         }
         let (entity, _) = tiles
@@ -372,11 +695,73 @@ fn upgrade_hex(
             .find(|(_, c)| **c == selected.coordinate)
             .expect("already checked tile for existence");
         shops.0 += 1;
+        money.send(ChangeMoneyEvent(
+            -(SHOP_PRICE as i32),
+            MoneyCategory::Purchase,
+        ));
+        popups.send(MoneyPopupEvent(selected.coordinate, -(SHOP_PRICE as i32)));
         map.tiles.insert(selected.coordinate, State::BreakShop);
         commands.entity(entity).despawn_recursive();
-        spawn_tile(&mut commands, selected.coordinate, State::BreakShop);
+        spawn_tile(&mut commands, selected.coordinate, State::BreakShop, &style);
         let (x, y) = selected.coordinate.to_pixel(Spacing::FlatTop(SIZE));
-        emitter_events.send(StartUpgradeEmitter(Vec3::new(x, y, 0.2)));
+        emitter_events.send(StartEmitter(particles::UPGRADE, Vec3::new(x, y, 0.2)));
+        history.0.push_back(Placement {
+            coordinate: selected.coordinate,
+            previous,
+            placed_at: daytime.ticks(),
+        });
+        if history.0.len() > MAX_UNDO_STEPS {
+            history.0.pop_front();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn undo_placement(
+    mut commands: Commands,
+    mut actions: EventReader<ActionEvent>,
+    mut history: ResMut<PlacementHistory>,
+    daytime: Res<Daytime>,
+    mut map: ResMut<Map>,
+    tiles: Query<(Entity, &Coordinate), With<SelectableTile>>,
+    mut shops: ResMut<CoffeeShops>,
+    mut money: EventWriter<ChangeMoneyEvent>,
+    mut popups: EventWriter<MoneyPopupEvent>,
+    style: Res<TileStyle>,
+) {
+    for ActionEvent(action) in actions.iter() {
+        if *action != Action::Undo {
+            continue;
+        }
+        let placement = if let Some(x) = history.0.pop_back() {
+            x
+        } else {
+            log::debug!("Nothing to undo");
+            continue;
+        };
+        if daytime.ticks() - placement.placed_at > UNDO_WINDOW_TICKS {
+            log::debug!("Last placement is too old to undo");
+            history.0.clear();
+            continue;
+        }
+        let c = placement.coordinate;
+        if let Some((entity, _)) = tiles.iter().find(|(_, coord)| **coord == c) {
+            commands.entity(entity).despawn_recursive();
+        }
+        shops.0 -= 1;
+        money.send(ChangeMoneyEvent(SHOP_PRICE as i32, MoneyCategory::Purchase));
+        popups.send(MoneyPopupEvent(c, SHOP_PRICE as i32));
+        map.tiles.insert(c, placement.previous);
+        spawn_tile(&mut commands, c, placement.previous, &style);
+    }
+}
+
+fn lock_placements(
+    mut events: EventReader<LockPlacementsEvent>,
+    mut history: ResMut<PlacementHistory>,
+) {
+    if events.iter().count() > 0 {
+        history.0.clear();
     }
 }
 
@@ -437,13 +822,47 @@ impl Plugin for FieldPlugin {
         app.add_startup_system(setup.system())
             .init_resource::<Option<SelectedHex>>()
             .init_resource::<Map>()
-            .init_resource::<NextRingTimer>()
+            .init_resource::<ExpansionTimer>()
+            .init_resource::<ExpansionCandidates>()
             .init_resource::<CoffeeShops>()
-            .add_system(generate_next_ring.system())
+            .init_resource::<PlacementHistory>()
+            .add_system(reroll_candidates.system().before("expand"))
+            .add_system(expand_map.system().label("expand"))
             .add_system(office_system.system())
             .add_system(return_worker.system())
+            .add_system(charge_upkeep.system())
             .add_system(process_coffees.system().label("coffee"))
+            .add_system(update_service_bars.system().after("coffee"))
+            .add_system(repaint_tiles.system())
             .add_system(upgrade_hex.system())
+            .add_system(lock_placements.system().before("undo"))
+            .add_system(undo_placement.system().label("undo"))
+            .add_event::<LockPlacementsEvent>()
             .add_system(select_hex.system());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_demand_means_no_workers() {
+        assert!(expected_ticks_between_workers(9, 0.).is_infinite());
+    }
+
+    #[test]
+    fn certain_spawn_takes_one_tick() {
+        let expected = expected_ticks_between_workers(HUNDRED_PERCENT_CHANCE, 1.);
+        assert!((expected - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn more_demand_means_shorter_gaps() {
+        let quiet = expected_ticks_between_workers(9, 0.5);
+        let normal = expected_ticks_between_workers(9, 1.);
+        let busy = expected_ticks_between_workers(9, 1.5);
+        assert!(quiet > normal && normal > busy);
+        assert!(busy >= 1.);
+    }
+}
@@ -0,0 +1,555 @@
+use bevy::{
+    log,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::config::{self, Section};
+
+const BINDINGS_PATH: &str = "bindings.cfg";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Place,
+    Cancel,
+    Pause,
+    SpeedUp,
+    ToggleOverlay,
+    Rebind,
+    Undo,
+    CyclePalette,
+    TogglePatterns,
+    Export,
+}
+
+const ACTIONS: [Action; 10] = [
+    Action::Place,
+    Action::Undo,
+    Action::Cancel,
+    Action::Pause,
+    Action::SpeedUp,
+    Action::ToggleOverlay,
+    Action::CyclePalette,
+    Action::TogglePatterns,
+    Action::Export,
+    Action::Rebind,
+];
+
+impl Action {
+    fn key(&self) -> &'static str {
+        match self {
+            Action::Place => "place",
+            Action::Cancel => "cancel",
+            Action::Pause => "pause",
+            Action::SpeedUp => "speed_up",
+            Action::ToggleOverlay => "toggle_overlay",
+            Action::Rebind => "rebind",
+            Action::Undo => "undo",
+            Action::CyclePalette => "cycle_palette",
+            Action::TogglePatterns => "toggle_patterns",
+            Action::Export => "export",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Action::Place => "Place shop",
+            Action::Cancel => "Cancel",
+            Action::Pause => "Pause",
+            Action::SpeedUp => "Speed up",
+            Action::ToggleOverlay => "Toggle overlay",
+            Action::Rebind => "Bindings",
+            Action::Undo => "Undo",
+            Action::CyclePalette => "Colour palette",
+            Action::TogglePatterns => "Tile patterns",
+            Action::Export => "Export map",
+        }
+    }
+
+    fn default_bindings(&self) -> Vec<Binding> {
+        use Binding::*;
+        match self {
+            Action::Place => vec![
+                Mouse(MouseButton::Left),
+                Key(KeyCode::U),
+                Gamepad(GamepadButtonType::South),
+            ],
+            Action::Cancel => vec![
+                Key(KeyCode::Escape),
+                Mouse(MouseButton::Right),
+                Gamepad(GamepadButtonType::East),
+            ],
+            Action::Pause => vec![Key(KeyCode::Space), Gamepad(GamepadButtonType::Start)],
+            Action::SpeedUp => vec![Key(KeyCode::Tab), Gamepad(GamepadButtonType::RightTrigger)],
+            Action::ToggleOverlay => vec![Key(KeyCode::O), Gamepad(GamepadButtonType::North)],
+            Action::Rebind => vec![Key(KeyCode::F1), Gamepad(GamepadButtonType::Select)],
+            Action::Undo => vec![Key(KeyCode::Z), Gamepad(GamepadButtonType::West)],
+            Action::CyclePalette => vec![Key(KeyCode::C), Gamepad(GamepadButtonType::LeftTrigger)],
+            Action::TogglePatterns => vec![Key(KeyCode::V), Gamepad(GamepadButtonType::LeftThumb)],
+            Action::Export => vec![Key(KeyCode::F2), Gamepad(GamepadButtonType::RightThumb)],
+        }
+    }
+}
+
+pub struct ActionEvent(pub Action);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+macro_rules! key_codes {
+    ($($key:ident),* $(,)?) => {
+        fn parse_key(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_codes! {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K,
+    L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10,
+    F11, F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space,
+    Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6,
+    Numpad7, Numpad8, Numpad9, AbntC1, AbntC2, NumpadAdd, Apostrophe, Apps, Asterisk, Plus, At, Ax,
+    Backslash, Calculator, Capital, Colon, Comma, Convert, NumpadDecimal, NumpadDivide, Equals,
+    Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift, LWin, Mail, MediaSelect, MediaStop,
+    Minus, NumpadMultiply, Mute, MyComputer, NavigateForward, NavigateBackward, NextTrack,
+    NoConvert, NumpadComma, NumpadEnter, NumpadEquals, Oem102, Period, PlayPause, Power, PrevTrack,
+    RAlt, RBracket, RControl, RShift, RWin, Semicolon, Slash, Sleep, Stop, NumpadSubtract, Sysrq,
+    Tab, Underline, Unlabeled, VolumeDown, VolumeUp, Wake, WebBack, WebFavorites, WebForward,
+    WebHome, WebRefresh, WebSearch, WebStop, Yen, Copy, Paste, Cut,
+}
+
+fn parse_mouse(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        other => other.parse().ok().map(MouseButton::Other),
+    }
+}
+
+fn parse_gamepad(name: &str) -> Option<GamepadButtonType> {
+    use GamepadButtonType::*;
+    let button = match name {
+        "South" => South,
+        "East" => East,
+        "North" => North,
+        "West" => West,
+        "C" => C,
+        "Z" => Z,
+        "LeftTrigger" => LeftTrigger,
+        "LeftTrigger2" => LeftTrigger2,
+        "RightTrigger" => RightTrigger,
+        "RightTrigger2" => RightTrigger2,
+        "Select" => Select,
+        "Start" => Start,
+        "Mode" => Mode,
+        "LeftThumb" => LeftThumb,
+        "RightThumb" => RightThumb,
+        "DPadUp" => DPadUp,
+        "DPadDown" => DPadDown,
+        "DPadLeft" => DPadLeft,
+        "DPadRight" => DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
+
+impl Binding {
+    fn same_device(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let (device, name) = s.split_once(':')?;
+        match device.trim() {
+            "key" => parse_key(name.trim()).map(Binding::Key),
+            "mouse" => parse_mouse(name.trim()).map(Binding::Mouse),
+            "pad" => parse_gamepad(name.trim()).map(Binding::Gamepad),
+            _ => None,
+        }
+    }
+
+    fn serialize(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("key:{:?}", key),
+            Binding::Mouse(MouseButton::Other(n)) => format!("mouse:{}", n),
+            Binding::Mouse(button) => format!("mouse:{:?}", button),
+            Binding::Gamepad(button) => format!("pad:{:?}", button),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+
+    fn just_pressed(&self, input: &RawInput) -> bool {
+        match *self {
+            Binding::Key(key) => input.keys.just_pressed(key),
+            Binding::Mouse(button) => input.mouse.just_pressed(button),
+            Binding::Gamepad(button) => input
+                .gamepads
+                .0
+                .iter()
+                .any(|&pad| input.pad_buttons.just_pressed(GamepadButton(pad, button))),
+        }
+    }
+}
+
+pub struct InputBindings(HashMap<Action, Vec<Binding>>);
+
+impl InputBindings {
+    fn label(&self, action: Action) -> String {
+        let bindings = self
+            .0
+            .get(&action)
+            .map(|bs| bs.iter().map(Binding::label).collect::<Vec<_>>().join(", "))
+            .unwrap_or_default();
+        format!("{}: {}", action.label(), bindings)
+    }
+
+    /// Gives `binding` to `action` in place of its binding for the same device,
+    /// swapping bindings with whichever action had it before
+    fn rebind(&mut self, action: Action, binding: Binding) {
+        let list = self.0.entry(action).or_default();
+        let replaced: Vec<_> = list
+            .iter()
+            .copied()
+            .filter(|b| b.same_device(&binding) && *b != binding)
+            .collect();
+        list.retain(|b| !b.same_device(&binding));
+        list.push(binding);
+        for (other, list) in self.0.iter_mut() {
+            if *other == action || !list.contains(&binding) {
+                continue;
+            }
+            log::debug!("Swapping {:?} from {:?} to {:?}", binding, other, replaced);
+            list.retain(|b| *b != binding);
+            list.extend(replaced.iter().copied());
+        }
+    }
+
+    fn save(&self) {
+        let mut section = Section::default();
+        for action in ACTIONS.iter() {
+            let bindings = self.0.get(action).map(Vec::as_slice).unwrap_or_default();
+            let value = bindings
+                .iter()
+                .map(Binding::serialize)
+                .collect::<Vec<_>>()
+                .join(", ");
+            section.set(action.key(), value);
+        }
+        config::save(BINDINGS_PATH, &[section]);
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut bindings: HashMap<_, _> = ACTIONS
+            .iter()
+            .map(|action| (*action, action.default_bindings()))
+            .collect();
+        let sections = config::load(BINDINGS_PATH).unwrap_or_default();
+        for section in sections.iter() {
+            for action in ACTIONS.iter() {
+                let value = if let Some(x) = section.get(action.key()) {
+                    x
+                } else {
+                    continue;
+                };
+                let parsed = value
+                    .split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .filter_map(|s| {
+                        let binding = Binding::parse(s);
+                        if binding.is_none() {
+                            log::warn!("Unknown binding for {}: {}", action.key(), s.trim());
+                        }
+                        binding
+                    })
+                    .collect();
+                bindings.insert(*action, parsed);
+            }
+        }
+        Self(bindings)
+    }
+}
+
+#[derive(Default)]
+struct ConnectedGamepads(HashSet<Gamepad>);
+
+struct RawInput<'a> {
+    keys: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    pad_buttons: &'a Input<GamepadButton>,
+    gamepads: &'a ConnectedGamepads,
+}
+
+impl RawInput<'_> {
+    fn any_just_pressed(&self) -> Option<Binding> {
+        if let Some(key) = self.keys.get_just_pressed().next() {
+            return Some(Binding::Key(*key));
+        }
+        if let Some(button) = self.mouse.get_just_pressed().next() {
+            return Some(Binding::Mouse(*button));
+        }
+        self.pad_buttons
+            .get_just_pressed()
+            .next()
+            .map(|GamepadButton(_, button)| Binding::Gamepad(*button))
+    }
+}
+
+#[derive(Default)]
+struct RebindScreen {
+    root: Option<Entity>,
+    waiting: Option<Action>,
+}
+
+struct RebindButton(Action);
+struct RebindText(Action);
+
+struct RebindMaterials {
+    card: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    waiting: Handle<ColorMaterial>,
+}
+
+impl FromWorld for RebindMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        RebindMaterials {
+            card: materials.add(Color::rgb_u8(230, 245, 255).into()),
+            button: materials.add(Color::rgb_u8(200, 220, 240).into()),
+            waiting: materials.add(Color::rgb_u8(255, 220, 150).into()),
+        }
+    }
+}
+
+fn track_gamepads(mut gamepads: ResMut<ConnectedGamepads>, mut events: EventReader<GamepadEvent>) {
+    for GamepadEvent(pad, event_type) in events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                gamepads.0.insert(*pad);
+            }
+            GamepadEventType::Disconnected => {
+                gamepads.0.remove(pad);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn dispatch_actions(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    screen: Res<RebindScreen>,
+    mut events: EventWriter<ActionEvent>,
+) {
+    if screen.waiting.is_some() {
+        return;
+    }
+    let input = RawInput {
+        keys: &keys,
+        mouse: &mouse,
+        pad_buttons: &pad_buttons,
+        gamepads: &gamepads,
+    };
+    for action in ACTIONS.iter() {
+        // only menu actions go through while the bindings screen is open
+        if screen.root.is_some() && !matches!(action, Action::Cancel | Action::Rebind) {
+            continue;
+        }
+        let pressed = bindings
+            .0
+            .get(action)
+            .map(|bs| bs.iter().any(|b| b.just_pressed(&input)))
+            .unwrap_or(false);
+        if pressed {
+            events.send(ActionEvent(*action));
+        }
+    }
+}
+
+fn toggle_rebind_screen(
+    mut commands: Commands,
+    mut actions: EventReader<ActionEvent>,
+    mut screen: ResMut<RebindScreen>,
+    bindings: Res<InputBindings>,
+    materials: Res<RebindMaterials>,
+    asset_server: Res<AssetServer>,
+) {
+    for ActionEvent(action) in actions.iter() {
+        match (action, screen.root) {
+            (Action::Rebind, None) => {}
+            (Action::Rebind, Some(root)) | (Action::Cancel, Some(root)) => {
+                commands.entity(root).despawn_recursive();
+                screen.root = None;
+                screen.waiting = None;
+                continue;
+            }
+            _ => continue,
+        }
+        let font = asset_server.load("FiraSans-Bold.ttf");
+        let style = TextStyle {
+            font,
+            font_size: 30.0,
+            color: Color::BLACK,
+        };
+        let root = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Percent(30.),
+                        top: Val::Px(100.),
+                        ..Default::default()
+                    },
+                    flex_direction: FlexDirection::ColumnReverse,
+                    padding: Rect::all(Val::Px(10.)),
+                    align_items: AlignItems::Stretch,
+                    ..Default::default()
+                },
+                material: materials.card.clone(),
+                ..Default::default()
+            })
+            .with_children(|ec| {
+                ec.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Click an action, then press a key or button, Cancel backs out",
+                        style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+                for action in ACTIONS.iter() {
+                    ec.spawn_bundle(ButtonBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(4.)),
+                            padding: Rect::all(Val::Px(6.)),
+                            ..Default::default()
+                        },
+                        material: materials.button.clone(),
+                        ..Default::default()
+                    })
+                    .insert(RebindButton(*action))
+                    .with_children(|ec| {
+                        ec.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                bindings.label(*action),
+                                style.clone(),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(RebindText(*action));
+                    });
+                }
+            })
+            .id();
+        screen.root = Some(root);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rebind(
+    mut screen: ResMut<RebindScreen>,
+    mut bindings: ResMut<InputBindings>,
+    materials: Res<RebindMaterials>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    interactions: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+    mut buttons: Query<(&RebindButton, &mut Handle<ColorMaterial>)>,
+    mut texts: Query<(&RebindText, &mut Text)>,
+) {
+    if screen.root.is_none() {
+        return;
+    }
+    if let Some(action) = screen.waiting {
+        let input = RawInput {
+            keys: &keys,
+            mouse: &mouse,
+            pad_buttons: &pad_buttons,
+            gamepads: &gamepads,
+        };
+        let binding = if let Some(x) = input.any_just_pressed() {
+            x
+        } else {
+            return;
+        };
+        let cancelled = bindings
+            .0
+            .get(&Action::Cancel)
+            .map(|bs| bs.contains(&binding))
+            .unwrap_or(false);
+        if cancelled {
+            log::debug!("Rebinding {:?} cancelled", action);
+        } else {
+            log::debug!("Rebound {:?} to {:?}", action, binding);
+            bindings.rebind(action, binding);
+            bindings.save();
+        }
+        screen.waiting = None;
+        // a swap changes the labels of another action too
+        for (RebindText(a), mut text) in texts.iter_mut() {
+            text.sections[0].value = bindings.label(*a);
+        }
+        for (RebindButton(a), mut material) in buttons.iter_mut() {
+            if *a == action {
+                *material = materials.button.clone();
+            }
+        }
+        return;
+    }
+    let clicked = interactions
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, RebindButton(action))| *action);
+    // the click itself is a fresh mouse press, so capturing starts on the next frame
+    if let Some(action) = clicked {
+        screen.waiting = Some(action);
+        for (RebindButton(a), mut material) in buttons.iter_mut() {
+            if *a == action {
+                *material = materials.waiting.clone();
+            }
+        }
+        for (RebindText(a), mut text) in texts.iter_mut() {
+            if *a == action {
+                text.sections[0].value = format!("{}: ...", action.label());
+            }
+        }
+    }
+}
+
+pub struct InputPlugin;
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<ConnectedGamepads>()
+            .init_resource::<RebindScreen>()
+            .init_resource::<RebindMaterials>()
+            .add_system(track_gamepads.system())
+            .add_system(dispatch_actions.system().label("actions"))
+            .add_system(toggle_rebind_screen.system().after("actions"))
+            .add_system(rebind.system().after("actions"))
+            .add_event::<ActionEvent>();
+    }
+}
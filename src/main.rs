@@ -1,18 +1,49 @@
+mod animation;
+mod calendar;
+mod config;
+mod daylight;
 mod daytime;
+mod difficulty;
+mod expansion_preview;
+mod export;
 mod field;
-mod overwait_particles;
+mod input;
+mod minimap;
+mod notifications;
+mod objectives;
+mod overlay;
+mod palette;
+mod particles;
+mod popups;
+mod preview;
+mod reputation;
 mod ui;
-mod upgrade_particles;
 mod utils;
+mod weather;
 mod workers;
 
+use animation::AnimationPlugin;
 use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*, text::TextPlugin};
 use bevy_prototype_lyon::prelude::*;
+use calendar::CalendarPlugin;
+use daylight::DaylightPlugin;
 use daytime::DaytimePlugin;
+use difficulty::DifficultyPlugin;
+use expansion_preview::ExpansionPreviewPlugin;
+use export::ExportPlugin;
 use field::FieldPlugin;
-use overwait_particles::OverwaitParticlesPlugin;
+use input::InputPlugin;
+use minimap::MinimapPlugin;
+use notifications::NotificationsPlugin;
+use objectives::ObjectivesPlugin;
+use overlay::OverlayPlugin;
+use palette::PalettePlugin;
+use particles::ParticlesPlugin;
+use popups::PopupsPlugin;
+use preview::PreviewPlugin;
+use reputation::ReputationPlugin;
 use ui::UiPlugin;
-use upgrade_particles::UpgradeParticlesPlugin;
+use weather::WeatherPlugin;
 use workers::WorkerPlugin;
 
 fn main() {
@@ -23,12 +54,27 @@ fn main() {
         .add_plugin(TextPlugin)
         .add_startup_system(setup.system())
         .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(PalettePlugin)
         .add_plugin(UiPlugin)
         .add_plugin(FieldPlugin)
         .add_plugin(DaytimePlugin)
+        .add_plugin(CalendarPlugin)
+        .add_plugin(WeatherPlugin)
+        .add_plugin(DaylightPlugin)
         .add_plugin(WorkerPlugin)
-        .add_plugin(UpgradeParticlesPlugin)
-        .add_plugin(OverwaitParticlesPlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(PreviewPlugin)
+        .add_plugin(ExpansionPreviewPlugin)
+        .add_plugin(OverlayPlugin)
+        .add_plugin(ParticlesPlugin)
+        .add_plugin(PopupsPlugin)
+        .add_plugin(NotificationsPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(ExportPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_plugin(ReputationPlugin)
+        .add_plugin(ObjectivesPlugin)
         .run();
 }
 
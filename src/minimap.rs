@@ -0,0 +1,280 @@
+use bevy::{core::FixedTimestep, prelude::*, ui::FocusPolicy, utils::HashMap};
+use hex2d::{Coordinate, Spacing};
+
+use crate::{
+    field::{Map, State, SIZE},
+    palette::TileStyle,
+    workers::Worker,
+    MainCamera,
+};
+
+const MINIMAP_SIZE: f32 = 200.;
+const TILE_SCALE: f32 = 1.6;
+const DOT_MIN: f32 = 2.;
+const DOT_PER_WORKER: f32 = 1.5;
+
+struct MinimapRoot;
+// Layers keep tiles below worker dots and both below the viewport
+struct TileLayer;
+struct DotLayer;
+struct MinimapTile;
+struct MinimapDot;
+struct MinimapViewport;
+
+// World units per minimap pixel, recomputed whenever the map grows
+struct MinimapScale(f32);
+
+impl Default for MinimapScale {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+impl MinimapScale {
+    fn to_minimap(&self, world: Vec2) -> Vec2 {
+        world / self.0 + Vec2::splat(MINIMAP_SIZE / 2.)
+    }
+
+    fn to_world(&self, minimap: Vec2) -> Vec2 {
+        (minimap - Vec2::splat(MINIMAP_SIZE / 2.)) * self.0
+    }
+}
+
+struct MinimapMaterials {
+    background: Handle<ColorMaterial>,
+    layer: Handle<ColorMaterial>,
+    dot: Handle<ColorMaterial>,
+    viewport: Handle<ColorMaterial>,
+    tiles: HashMap<State, Handle<ColorMaterial>>,
+}
+
+impl FromWorld for MinimapMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        MinimapMaterials {
+            background: materials.add(Color::rgba(0.1, 0.1, 0.1, 0.8).into()),
+            layer: materials.add(Color::NONE.into()),
+            dot: materials.add(Color::WHITE.into()),
+            viewport: materials.add(Color::rgba(1., 1., 1., 0.25).into()),
+            tiles: HashMap::default(),
+        }
+    }
+}
+
+fn square(center: Vec2, side: f32) -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+            left: Val::Px(center.x - side / 2.),
+            bottom: Val::Px(center.y - side / 2.),
+            ..Default::default()
+        },
+        size: Size::new(Val::Px(side), Val::Px(side)),
+        ..Default::default()
+    }
+}
+
+fn setup(mut commands: Commands, materials: Res<MinimapMaterials>) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                ..Default::default()
+            },
+            material: materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(MinimapRoot)
+        .with_children(|ec| {
+            let layer = NodeBundle {
+                style: square(Vec2::splat(MINIMAP_SIZE / 2.), MINIMAP_SIZE),
+                material: materials.layer.clone(),
+                ..Default::default()
+            };
+            ec.spawn_bundle(layer.clone())
+                .insert(FocusPolicy::Pass)
+                .insert(TileLayer);
+            ec.spawn_bundle(layer)
+                .insert(FocusPolicy::Pass)
+                .insert(DotLayer);
+            ec.spawn_bundle(NodeBundle {
+                style: square(Vec2::ZERO, 0.),
+                material: materials.viewport.clone(),
+                ..Default::default()
+            })
+            .insert(FocusPolicy::Pass)
+            .insert(MinimapViewport);
+        });
+}
+
+fn draw_tiles(
+    mut commands: Commands,
+    map: Res<Map>,
+    style: Res<TileStyle>,
+    mut scale: ResMut<MinimapScale>,
+    mut materials: ResMut<MinimapMaterials>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    layer: Query<Entity, With<TileLayer>>,
+    tiles: Query<Entity, With<MinimapTile>>,
+) {
+    if !map.is_changed() && !style.is_changed() {
+        return;
+    }
+    let layer = if let Ok(x) = layer.single() {
+        x
+    } else {
+        return;
+    };
+    if style.is_changed() {
+        materials.tiles.clear();
+    }
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let extent = map
+        .tiles()
+        .map(|(c, _)| {
+            let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+            x.abs().max(y.abs())
+        })
+        .fold(0_f32, f32::max)
+        + SIZE;
+    scale.0 = extent * 2. / MINIMAP_SIZE;
+    let side = SIZE * TILE_SCALE / scale.0;
+    let mut nodes = vec![];
+    for (c, state) in map.tiles() {
+        let material = materials
+            .tiles
+            .entry(state)
+            .or_insert_with(|| color_materials.add(style.palette.color(state).into()))
+            .clone();
+        let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+        let node = commands
+            .spawn_bundle(NodeBundle {
+                style: square(scale.to_minimap(Vec2::new(x, y)), side),
+                material,
+                ..Default::default()
+            })
+            .insert(FocusPolicy::Pass)
+            .insert(MinimapTile)
+            .id();
+        nodes.push(node);
+    }
+    commands.entity(layer).push_children(&nodes);
+}
+
+fn draw_worker_dots(
+    mut commands: Commands,
+    scale: Res<MinimapScale>,
+    materials: Res<MinimapMaterials>,
+    workers: Query<&Transform, With<Worker>>,
+    layer: Query<Entity, With<DotLayer>>,
+    dots: Query<Entity, With<MinimapDot>>,
+) {
+    let layer = if let Ok(x) = layer.single() {
+        x
+    } else {
+        return;
+    };
+    for entity in dots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let mut density: HashMap<Coordinate, u32> = HashMap::default();
+    for transform in workers.iter() {
+        let t = transform.translation;
+        let c = Coordinate::from_pixel(t.x, t.y, Spacing::FlatTop(SIZE));
+        *density.entry(c).or_insert(0) += 1;
+    }
+    let max_side = SIZE * TILE_SCALE / scale.0;
+    let mut nodes = vec![];
+    for (c, count) in density {
+        let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+        let side = (DOT_MIN + DOT_PER_WORKER * (count as f32).sqrt()).min(max_side);
+        let node = commands
+            .spawn_bundle(NodeBundle {
+                style: square(scale.to_minimap(Vec2::new(x, y)), side),
+                material: materials.dot.clone(),
+                ..Default::default()
+            })
+            .insert(FocusPolicy::Pass)
+            .insert(MinimapDot)
+            .id();
+        nodes.push(node);
+    }
+    commands.entity(layer).push_children(&nodes);
+}
+
+fn update_viewport(
+    windows: Res<Windows>,
+    scale: Res<MinimapScale>,
+    camera: Query<&Transform, With<MainCamera>>,
+    mut viewport: Query<&mut Style, With<MinimapViewport>>,
+) {
+    let wnd = windows.get_primary().unwrap();
+    let camera = if let Ok(x) = camera.single() {
+        x
+    } else {
+        return;
+    };
+    let half = Vec2::new(wnd.width(), wnd.height()) / 2. * camera.scale.truncate();
+    let center = camera.translation.truncate();
+    let bounds = Vec2::splat(MINIMAP_SIZE);
+    let min = scale.to_minimap(center - half).max(Vec2::ZERO).min(bounds);
+    let max = scale.to_minimap(center + half).max(Vec2::ZERO).min(bounds);
+    for mut style in viewport.iter_mut() {
+        style.position.left = Val::Px(min.x);
+        style.position.bottom = Val::Px(min.y);
+        style.size = Size::new(Val::Px(max.x - min.x), Val::Px(max.y - min.y));
+    }
+}
+
+// Clicking or dragging over the minimap moves the camera there
+fn jump_camera(
+    windows: Res<Windows>,
+    scale: Res<MinimapScale>,
+    root: Query<(&Interaction, &Node, &GlobalTransform), With<MinimapRoot>>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let (interaction, node, transform) = if let Ok(x) = root.single() {
+        x
+    } else {
+        return;
+    };
+    if *interaction != Interaction::Clicked {
+        return;
+    }
+    let cursor = if let Some(x) = windows.get_primary().and_then(|w| w.cursor_position()) {
+        x
+    } else {
+        return;
+    };
+    let corner = transform.translation.truncate() - node.size / 2.;
+    let world = scale.to_world(cursor - corner);
+    for mut transform in camera.iter_mut() {
+        transform.translation.x = world.x;
+        transform.translation.y = world.y;
+    }
+}
+
+pub struct MinimapPlugin;
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MinimapMaterials>()
+            .init_resource::<MinimapScale>()
+            .add_startup_system(setup.system())
+            .add_system(draw_tiles.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(4.))
+                    .with_system(draw_worker_dots.system()),
+            )
+            .add_system(update_viewport.system())
+            .add_system(jump_camera.system());
+    }
+}
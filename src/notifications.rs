@@ -0,0 +1,393 @@
+use bevy::{prelude::*, utils::HashSet};
+use hex2d::{Coordinate, Spacing};
+
+use crate::{
+    calendar::Calendar,
+    daytime::{Daytime, NewDayEvent, Weekday},
+    export::MapExportedEvent,
+    field::{CoffeeShops, Map, SIZE},
+    input::{Action, ActionEvent},
+    objectives::ObjectiveDoneEvent,
+    palette::TileStyle,
+    reputation::{Reputation, LOW_REPUTATION},
+    ui::{fit_camera, CameraFit, DebtEvent, MapExpandedEvent, Money},
+    weather::{CurrentWeather, Weather},
+    workers::OverwaitEvent,
+    MainCamera,
+};
+
+const TOAST_SECS: f32 = 8.;
+const MAX_TOASTS: usize = 5;
+const LOW_MONEY: u32 = 3;
+
+pub struct NotifyEvent {
+    pub text: String,
+    pub focus: Option<Coordinate>,
+}
+
+impl NotifyEvent {
+    fn new(text: String, focus: Option<Coordinate>) -> Self {
+        Self { text, focus }
+    }
+}
+
+struct Toast {
+    focus: Option<Coordinate>,
+    age: f32,
+}
+
+#[derive(Default)]
+struct NotificationFeed {
+    root: Option<Entity>,
+}
+
+struct NotificationMaterials {
+    feed: Handle<ColorMaterial>,
+    toast: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+}
+
+impl FromWorld for NotificationMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        NotificationMaterials {
+            feed: materials.add(Color::NONE.into()),
+            toast: materials.add(Color::rgb_u8(230, 245, 255).into()),
+            hovered: materials.add(Color::rgb_u8(200, 220, 240).into()),
+        }
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut feed: ResMut<NotificationFeed>,
+    materials: Res<NotificationMaterials>,
+) {
+    let root = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(50.),
+                    ..Default::default()
+                },
+                // newest toasts are pushed last and end up on top
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            material: materials.feed.clone(),
+            ..Default::default()
+        })
+        .id();
+    feed.root = Some(root);
+}
+
+fn announce_expansions(
+    mut expansions: EventReader<MapExpandedEvent>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    for MapExpandedEvent(sector, center) in expansions.iter() {
+        let text = format!("The city grew to the {}", sector);
+        notify.send(NotifyEvent::new(text, Some(*center)));
+    }
+}
+
+fn announce_shop_cap(
+    shops: Res<CoffeeShops>,
+    mut last_cap: Local<Option<u32>>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    if !shops.is_changed() {
+        return;
+    }
+    if let Some(cap) = *last_cap {
+        if shops.1 > cap {
+            let text = format!("You can now run {} coffee shops", shops.1);
+            notify.send(NotifyEvent::new(text, None));
+        }
+    }
+    *last_cap = Some(shops.1);
+}
+
+fn announce_unreachable_offices(
+    map: Res<Map>,
+    mut known: Local<HashSet<Coordinate>>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    if !map.is_changed() {
+        return;
+    }
+    let unreachable: HashSet<_> = map
+        .offices()
+        .filter(|office| map.find_path(*office, |c| map.is_coffee(c)).is_none())
+        .collect();
+    let new: Vec<_> = unreachable.difference(&known).copied().collect();
+    if let Some(first) = new.first() {
+        let text = if new.len() == 1 {
+            "An office cannot reach any coffee shop".to_string()
+        } else {
+            format!("{} offices cannot reach any coffee shop", new.len())
+        };
+        notify.send(NotifyEvent::new(text, Some(*first)));
+    }
+    *known = unreachable;
+}
+
+fn announce_overwaits(
+    mut overwaits: EventReader<OverwaitEvent>,
+    mut seen: Local<HashSet<Coordinate>>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    for OverwaitEvent(shop) in overwaits.iter() {
+        if seen.insert(*shop) {
+            let text = "Workers are giving up on a queue".to_string();
+            notify.send(NotifyEvent::new(text, Some(*shop)));
+        }
+    }
+}
+
+fn announce_days(
+    daytime: Res<Daytime>,
+    calendar: Res<Calendar>,
+    mut days: EventReader<NewDayEvent>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    for NewDayEvent(day) in days.iter() {
+        let weekday = Weekday::of_day(*day).name();
+        let mut text = format!("{}, day {} begins", weekday, day);
+        let special = calendar.today(&daytime);
+        if !special.is_empty() {
+            text.push_str(&format!(": {}", special.join(", ")));
+        }
+        notify.send(NotifyEvent::new(text, None));
+    }
+}
+
+fn announce_low_reputation(
+    reputation: Res<Reputation>,
+    mut was_low: Local<bool>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    if !reputation.is_changed() {
+        return;
+    }
+    let is_low = reputation.0 < LOW_REPUTATION;
+    if is_low && !*was_low {
+        let text = "Reputation is low, workers may stop coming".to_string();
+        notify.send(NotifyEvent::new(text, None));
+    }
+    *was_low = is_low;
+}
+
+fn announce_objectives(
+    mut done: EventReader<ObjectiveDoneEvent>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    for ObjectiveDoneEvent(description) in done.iter() {
+        let text = format!("Objective complete! {}", description);
+        notify.send(NotifyEvent::new(text, None));
+    }
+}
+
+fn announce_weather(weather: Res<CurrentWeather>, mut notify: EventWriter<NotifyEvent>) {
+    if weather.is_changed() && weather.0 != Weather::Clear {
+        let text = format!("{}: {}", weather.0.name(), weather.0.description());
+        notify.send(NotifyEvent::new(text, None));
+    }
+}
+
+fn announce_tile_style(
+    style: Res<TileStyle>,
+    mut loaded: Local<bool>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    // the style loaded from settings counts as a change too
+    if !*loaded {
+        *loaded = true;
+        return;
+    }
+    if style.is_changed() {
+        let patterns = if style.patterns { "on" } else { "off" };
+        let text = format!("Palette: {}, patterns {}", style.palette.label(), patterns);
+        notify.send(NotifyEvent::new(text, None));
+    }
+}
+
+fn announce_export(
+    mut exports: EventReader<MapExportedEvent>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    for MapExportedEvent(path) in exports.iter() {
+        notify.send(NotifyEvent::new(
+            format!("Map exported to {}.svg", path),
+            None,
+        ));
+    }
+}
+
+fn announce_debt(mut debts: EventReader<DebtEvent>, mut notify: EventWriter<NotifyEvent>) {
+    for DebtEvent(amount) in debts.iter() {
+        let text = format!("Cannot pay {}, it is due once money comes in", amount);
+        notify.send(NotifyEvent::new(text, None));
+    }
+}
+
+fn announce_low_money(
+    money: Res<Money>,
+    mut was_low: Local<bool>,
+    mut loaded: Local<bool>,
+    mut notify: EventWriter<NotifyEvent>,
+) {
+    // the starting balance counts as a change too, and only a drop below it is news
+    if !*loaded {
+        *loaded = true;
+        *was_low = money.0 < LOW_MONEY;
+        return;
+    }
+    if !money.is_changed() {
+        return;
+    }
+    let is_low = money.0 < LOW_MONEY;
+    if is_low && !*was_low {
+        let text = format!("Money is running low: {} left", money.0);
+        notify.send(NotifyEvent::new(text, None));
+    }
+    *was_low = is_low;
+}
+
+fn spawn_toasts(
+    mut commands: Commands,
+    mut events: EventReader<NotifyEvent>,
+    feed: Res<NotificationFeed>,
+    materials: Res<NotificationMaterials>,
+    asset_server: Res<AssetServer>,
+) {
+    let root = if let Some(x) = feed.root {
+        x
+    } else {
+        return;
+    };
+    let style = TextStyle {
+        font: asset_server.load("FiraSans-Bold.ttf"),
+        font_size: 24.0,
+        color: Color::BLACK,
+    };
+    for NotifyEvent { text, focus } in events.iter() {
+        let text = if focus.is_some() {
+            format!("{} (click to show)", text)
+        } else {
+            text.clone()
+        };
+        commands.entity(root).with_children(|ec| {
+            ec.spawn_bundle(ButtonBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(4.)),
+                    padding: Rect::all(Val::Px(6.)),
+                    ..Default::default()
+                },
+                material: materials.toast.clone(),
+                ..Default::default()
+            })
+            .insert(Toast {
+                focus: *focus,
+                age: 0.,
+            })
+            .with_children(|ec| {
+                ec.spawn_bundle(TextBundle {
+                    text: Text::with_section(text, style.clone(), Default::default()),
+                    ..Default::default()
+                });
+            });
+        });
+    }
+}
+
+fn expire_toasts(mut commands: Commands, time: Res<Time>, mut toasts: Query<(Entity, &mut Toast)>) {
+    let mut alive = vec![];
+    for (entity, mut toast) in toasts.iter_mut() {
+        toast.age += time.delta_seconds();
+        if toast.age >= TOAST_SECS {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            alive.push((toast.age, entity));
+        }
+    }
+    if alive.len() > MAX_TOASTS {
+        alive.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        for (_, entity) in alive.iter().take(alive.len() - MAX_TOASTS) {
+            commands.entity(*entity).despawn_recursive();
+        }
+    }
+}
+
+fn click_toasts(
+    mut commands: Commands,
+    materials: Res<NotificationMaterials>,
+    mut toasts: Query<
+        (Entity, &Interaction, &Toast, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    for (entity, interaction, toast, mut material) in toasts.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                if let Some(c) = toast.focus {
+                    let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+                    for mut transform in camera.iter_mut() {
+                        transform.translation.x = x;
+                        transform.translation.y = y;
+                    }
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+            Interaction::Hovered => *material = materials.hovered.clone(),
+            Interaction::None => *material = materials.toast.clone(),
+        }
+    }
+}
+
+fn recenter_camera(
+    mut actions: EventReader<ActionEvent>,
+    map: Res<Map>,
+    windows: Res<Windows>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+    mut fit: ResMut<CameraFit>,
+) {
+    let wnd = windows.get_primary().unwrap();
+    for ActionEvent(action) in actions.iter() {
+        if *action == Action::Cancel {
+            for mut transform in camera.iter_mut() {
+                fit_camera(&mut transform, &map, wnd, &mut fit);
+            }
+        }
+    }
+}
+
+pub struct NotificationsPlugin;
+impl Plugin for NotificationsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<NotificationFeed>()
+            .init_resource::<NotificationMaterials>()
+            .add_event::<NotifyEvent>()
+            .add_startup_system(setup.system())
+            .add_system(announce_expansions.system())
+            .add_system(announce_shop_cap.system())
+            .add_system(announce_unreachable_offices.system())
+            .add_system(announce_overwaits.system())
+            .add_system(announce_days.system())
+            .add_system(announce_objectives.system())
+            .add_system(announce_weather.system())
+            .add_system(announce_tile_style.system())
+            .add_system(announce_export.system())
+            .add_system(announce_debt.system())
+            .add_system(announce_low_money.system())
+            .add_system(announce_low_reputation.system())
+            .add_system(spawn_toasts.system())
+            .add_system(expire_toasts.system())
+            .add_system(click_toasts.system())
+            .add_system(recenter_camera.system());
+    }
+}
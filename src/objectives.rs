@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use hex2d::Coordinate;
+use rand::{prelude::SliceRandom, thread_rng, Rng};
+
+use crate::{
+    daytime::{Daytime, NewDayEvent, NewHourEvent},
+    field::{CoffeeShops, CoffeeTile, LockPlacementsEvent, Map, State},
+    ui::{ChangeMoneyEvent, MoneyCategory},
+    workers::{OverwaitEvent, ServedEvent},
+};
+
+const MIN_OBJECTIVES: usize = 2;
+const MAX_OBJECTIVES: usize = 3;
+const SERVE_TARGET_BASE: u32 = 20;
+const SERVE_TARGET_PER_DAY: u32 = 10;
+const CALM_HOURS: u32 = 2;
+const CALM_FIRST_START: u32 = 8;
+const CALM_LAST_START: u32 = 16;
+const MIN_SHOP_DISTANCE: i32 = 2;
+const MONEY_REWARD_BASE: u32 = 5;
+const MONEY_REWARD_PER_DAY: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    ServeWorkers { target: u32, served: u32 },
+    // no worker gives up from the first hour until the last one
+    NoOverwaits { from: u32, to: u32 },
+    ShopFarOut { distance: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reward {
+    Money(u32),
+    ShopSlot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Active,
+    Done,
+    Failed,
+}
+
+struct Objective {
+    goal: Goal,
+    reward: Reward,
+    status: Status,
+}
+
+impl Objective {
+    fn description(&self) -> String {
+        let goal = match self.goal {
+            Goal::ServeWorkers { target, served } => {
+                format!(
+                    "Serve {} workers ({}/{})",
+                    target,
+                    served.min(target),
+                    target
+                )
+            }
+            Goal::NoOverwaits { from, to } => {
+                format!("No give-ups between {:02}:00 and {:02}:00", from, to)
+            }
+            Goal::ShopFarOut { distance } => {
+                format!("Build a shop {}+ tiles from the center", distance)
+            }
+        };
+        let reward = match self.reward {
+            Reward::Money(amount) => format!("+{} money", amount),
+            Reward::ShopSlot => "+1 shop slot".to_string(),
+        };
+        format!("{}: {}", goal, reward)
+    }
+
+    fn color(&self) -> Color {
+        match self.status {
+            Status::Active => Color::BLACK,
+            Status::Done => Color::rgb_u8(0, 112, 74),
+            Status::Failed => Color::GRAY,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Objectives {
+    day: u32,
+    list: Vec<Objective>,
+}
+
+/// An objective was completed, with its description
+pub struct ObjectiveDoneEvent(pub String);
+
+fn center() -> Coordinate {
+    Coordinate::new(0, 0)
+}
+
+fn roll_objectives(day: u32, map: &Map) -> Vec<Objective> {
+    let mut rng = thread_rng();
+    let mut goals = vec![];
+    goals.push(Goal::ServeWorkers {
+        target: SERVE_TARGET_BASE + SERVE_TARGET_PER_DAY * (day - 1),
+        served: 0,
+    });
+    let from = rng.gen_range(CALM_FIRST_START..=CALM_LAST_START);
+    goals.push(Goal::NoOverwaits {
+        from,
+        to: from + CALM_HOURS,
+    });
+    // only ask for distances the map can offer right now
+    let farthest = map
+        .tiles()
+        .filter(|(_, state)| *state == State::Inactive)
+        .map(|(c, _)| c.distance(center()))
+        .max()
+        .unwrap_or(0);
+    if farthest >= MIN_SHOP_DISTANCE {
+        goals.push(Goal::ShopFarOut {
+            distance: rng.gen_range(MIN_SHOP_DISTANCE..=farthest),
+        });
+    }
+    goals.shuffle(&mut rng);
+    let count = rng
+        .gen_range(MIN_OBJECTIVES..=MAX_OBJECTIVES)
+        .min(goals.len());
+    goals
+        .into_iter()
+        .take(count)
+        .map(|goal| {
+            let reward = if rng.gen_bool(0.25) {
+                Reward::ShopSlot
+            } else {
+                Reward::Money(MONEY_REWARD_BASE + MONEY_REWARD_PER_DAY * (day - 1))
+            };
+            Objective {
+                goal,
+                reward,
+                status: Status::Active,
+            }
+        })
+        .collect()
+}
+
+fn new_objectives(
+    mut objectives: ResMut<Objectives>,
+    mut days: EventReader<NewDayEvent>,
+    map: Res<Map>,
+) {
+    // the first day has no event, it starts with the game
+    let mut day = if objectives.day == 0 { Some(1) } else { None };
+    for NewDayEvent(x) in days.iter() {
+        day = Some(*x);
+    }
+    if let Some(day) = day {
+        objectives.day = day;
+        objectives.list = roll_objectives(day, &map);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_objectives(
+    mut objectives: ResMut<Objectives>,
+    daytime: Res<Daytime>,
+    mut served: EventReader<ServedEvent>,
+    mut overwaits: EventReader<OverwaitEvent>,
+    mut hours: EventReader<NewHourEvent>,
+    new_shops: Query<&Coordinate, Added<CoffeeTile>>,
+    mut money: EventWriter<ChangeMoneyEvent>,
+    mut shops: ResMut<CoffeeShops>,
+    mut done_events: EventWriter<ObjectiveDoneEvent>,
+    mut lock: EventWriter<LockPlacementsEvent>,
+) {
+    let served = served.iter().count() as u32;
+    let overwaited = overwaits.iter().count() > 0;
+    let hours: Vec<u32> = hours.iter().map(|NewHourEvent(h)| *h).collect();
+    let farthest_new_shop = new_shops.iter().map(|c| c.distance(center())).max();
+    if served == 0 && !overwaited && hours.is_empty() && farthest_new_shop.is_none() {
+        return;
+    }
+    let hour = daytime.get_hours();
+    for objective in objectives.list.iter_mut() {
+        if objective.status != Status::Active {
+            continue;
+        }
+        let done = match &mut objective.goal {
+            Goal::ServeWorkers {
+                target,
+                served: count,
+            } => {
+                *count += served;
+                *count >= *target
+            }
+            Goal::NoOverwaits { from, to } => {
+                if overwaited && hour >= *from && hour < *to {
+                    objective.status = Status::Failed;
+                }
+                hours.contains(to)
+            }
+            Goal::ShopFarOut { distance } => {
+                farthest_new_shop.map(|x| x >= *distance).unwrap_or(false)
+            }
+        };
+        if !done || objective.status != Status::Active {
+            continue;
+        }
+        objective.status = Status::Done;
+        // undoing the shop would refund it and keep the reward
+        if let Goal::ShopFarOut { .. } = objective.goal {
+            lock.send(LockPlacementsEvent);
+        }
+        match objective.reward {
+            Reward::Money(amount) => {
+                money.send(ChangeMoneyEvent(amount as i32, MoneyCategory::Reward))
+            }
+            Reward::ShopSlot => shops.1 += 1,
+        }
+        done_events.send(ObjectiveDoneEvent(objective.description()));
+    }
+}
+
+struct ObjectivesCard;
+
+struct ObjectivesMaterials {
+    card: Handle<ColorMaterial>,
+}
+
+impl FromWorld for ObjectivesMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        ObjectivesMaterials {
+            card: materials.add(Color::rgb_u8(230, 245, 255).into()),
+        }
+    }
+}
+
+fn draw_objectives(
+    mut commands: Commands,
+    objectives: Res<Objectives>,
+    materials: Res<ObjectivesMaterials>,
+    asset_server: Res<AssetServer>,
+    cards: Query<Entity, With<ObjectivesCard>>,
+) {
+    if !objectives.is_changed() {
+        return;
+    }
+    for entity in cards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    let style = TextStyle {
+        font,
+        font_size: 20.0,
+        color: Color::BLACK,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    bottom: Val::Px(220.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.)),
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.card.clone(),
+            ..Default::default()
+        })
+        .insert(ObjectivesCard)
+        .with_children(|ec| {
+            let title = format!("Day {} objectives", objectives.day);
+            ec.spawn_bundle(TextBundle {
+                text: Text::with_section(title, style.clone(), Default::default()),
+                ..Default::default()
+            });
+            for objective in objectives.list.iter() {
+                let style = TextStyle {
+                    color: objective.color(),
+                    ..style.clone()
+                };
+                ec.spawn_bundle(TextBundle {
+                    text: Text::with_section(objective.description(), style, Default::default()),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+pub struct ObjectivesPlugin;
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Objectives>()
+            .init_resource::<ObjectivesMaterials>()
+            .add_event::<ObjectiveDoneEvent>()
+            .add_system(new_objectives.system().label("new objectives"))
+            .add_system(track_objectives.system().after("new objectives"))
+            .add_system(draw_objectives.system().after("new objectives"));
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use hex2d::Spacing;
+
+use crate::{
+    field::{build_hex_shape, Map, SIZE},
+    input::{Action, ActionEvent},
+    reputation::Reputation,
+    workers::{money_for_path, WorkerKinds},
+};
+
+#[derive(Default)]
+struct CoverageOverlay(bool);
+
+struct OverlayItem;
+
+fn toggle_overlay(mut overlay: ResMut<CoverageOverlay>, mut actions: EventReader<ActionEvent>) {
+    for ActionEvent(action) in actions.iter() {
+        if *action == Action::ToggleOverlay {
+            overlay.0 = !overlay.0;
+        }
+    }
+}
+
+fn draw_overlay(
+    mut commands: Commands,
+    overlay: Res<CoverageOverlay>,
+    map: Res<Map>,
+    kinds: Res<WorkerKinds>,
+    reputation: Res<Reputation>,
+    asset_server: Res<AssetServer>,
+    items: Query<Entity, With<OverlayItem>>,
+) {
+    if !overlay.is_changed() && !map.is_changed() && !reputation.is_changed() {
+        return;
+    }
+    for entity in items.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !overlay.0 {
+        return;
+    }
+    let style = TextStyle {
+        font: asset_server.load("FiraSans-Bold.ttf"),
+        font_size: 40.0,
+        color: Color::WHITE,
+    };
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    for office in map.offices() {
+        let path = map.find_path(office, |c| map.is_coffee(c));
+        let (label, highlight) = match path {
+            Some(path) => {
+                let highlight = if money_for_path(path.len()) == 0 {
+                    Some(Color::ORANGE)
+                } else {
+                    None
+                };
+                let money = kinds.expected_payout(path.len(), &reputation);
+                (format!("{} tiles\n+{:.1}", path.len(), money), highlight)
+            }
+            None => ("no shop".to_string(), Some(Color::CRIMSON)),
+        };
+        let (x, y) = office.to_pixel(Spacing::FlatTop(SIZE));
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(label, style.clone(), alignment),
+                transform: Transform::from_xyz(x, y, 0.45),
+                ..Default::default()
+            })
+            .insert(OverlayItem);
+        if let Some(color) = highlight {
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &build_hex_shape(),
+                    ShapeColors::new(color),
+                    DrawMode::Stroke(StrokeOptions::default().with_line_width(8.0)),
+                    Transform::from_xyz(x, y, 0.45),
+                ))
+                .insert(OverlayItem);
+        }
+    }
+}
+
+pub struct OverlayPlugin;
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CoverageOverlay>()
+            .add_system(toggle_overlay.system())
+            .add_system(draw_overlay.system());
+    }
+}
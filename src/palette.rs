@@ -0,0 +1,232 @@
+use bevy::{log, prelude::*};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    config::{self, Section},
+    field::State,
+    input::{Action, ActionEvent},
+};
+
+const SETTINGS_PATH: &str = "settings.cfg";
+const DISPLAY_SECTION: &str = "display";
+const PATTERN_WIDTH: f32 = 8.;
+const PATTERN_Z: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+const PALETTES: [Palette; 5] = [
+    Palette::Default,
+    Palette::Deuteranopia,
+    Palette::Protanopia,
+    Palette::Tritanopia,
+    Palette::HighContrast,
+];
+
+impl Palette {
+    fn key(self) -> &'static str {
+        match self {
+            Palette::Default => "default",
+            Palette::Deuteranopia => "deuteranopia",
+            Palette::Protanopia => "protanopia",
+            Palette::Tritanopia => "tritanopia",
+            Palette::HighContrast => "high_contrast",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Protanopia => "Protanopia",
+            Palette::Tritanopia => "Tritanopia",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    fn next(self) -> Self {
+        let n = PALETTES.iter().position(|p| *p == self).unwrap_or(0);
+        PALETTES[(n + 1) % PALETTES.len()]
+    }
+
+    // colour-safe choices are taken from the Okabe-Ito palette
+    pub fn color(self, state: State) -> Color {
+        match (self, state) {
+            (Palette::Default, State::Inactive) => Color::DARK_GRAY,
+            (Palette::Default, State::Active) => Color::rgb_u8(82, 151, 255),
+            (Palette::Default, State::BreakShop) => Color::rgb_u8(0, 112, 74),
+            (Palette::Deuteranopia, State::Inactive) => Color::rgb_u8(110, 110, 110),
+            (Palette::Deuteranopia, State::Active) => Color::rgb_u8(0, 114, 178),
+            (Palette::Deuteranopia, State::BreakShop) => Color::rgb_u8(230, 159, 0),
+            (Palette::Protanopia, State::Inactive) => Color::rgb_u8(100, 100, 100),
+            (Palette::Protanopia, State::Active) => Color::rgb_u8(86, 180, 233),
+            (Palette::Protanopia, State::BreakShop) => Color::rgb_u8(240, 228, 66),
+            (Palette::Tritanopia, State::Inactive) => Color::rgb_u8(120, 120, 120),
+            (Palette::Tritanopia, State::Active) => Color::rgb_u8(0, 158, 115),
+            (Palette::Tritanopia, State::BreakShop) => Color::rgb_u8(213, 94, 0),
+            (Palette::HighContrast, State::Inactive) => Color::rgb_u8(220, 220, 220),
+            (Palette::HighContrast, State::Active) => Color::rgb_u8(0, 70, 255),
+            (Palette::HighContrast, State::BreakShop) => Color::rgb_u8(255, 200, 0),
+            (_, State::Obstacle) => Color::BLACK,
+        }
+    }
+
+    fn pattern_color(self, state: State) -> Color {
+        match (self, state) {
+            (_, State::Obstacle) => Color::rgb(0.6, 0.6, 0.6),
+            (Palette::HighContrast, _) => Color::BLACK,
+            _ => Color::rgba(0., 0., 0., 0.6),
+        }
+    }
+}
+
+/// How tiles are painted, persisted in the user settings
+pub struct TileStyle {
+    pub palette: Palette,
+    // per-state icons on top of the fill colour
+    pub patterns: bool,
+}
+
+impl Default for TileStyle {
+    fn default() -> Self {
+        let mut style = TileStyle {
+            palette: Palette::Default,
+            patterns: false,
+        };
+        let sections = config::load(SETTINGS_PATH).unwrap_or_default();
+        let section = if let Some(x) = sections.iter().find(|s| s.name == DISPLAY_SECTION) {
+            x
+        } else {
+            return style;
+        };
+        if let Some(key) = section.get("palette") {
+            match PALETTES.iter().find(|p| p.key() == key) {
+                Some(palette) => style.palette = *palette,
+                None => log::warn!("Unknown palette in {}: {}", SETTINGS_PATH, key),
+            }
+        }
+        if let Some(x) = section.get_parsed("patterns") {
+            style.patterns = x;
+        }
+        style
+    }
+}
+
+impl TileStyle {
+    fn save(&self) {
+        // keep whatever other sections the settings file has
+        let mut sections: Vec<_> = config::load(SETTINGS_PATH)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| s.name != DISPLAY_SECTION)
+            .collect();
+        let mut section = Section::new(DISPLAY_SECTION);
+        section.set("palette", self.palette.key().to_string());
+        section.set("patterns", self.patterns.to_string());
+        sections.push(section);
+        config::save(SETTINGS_PATH, &sections);
+    }
+}
+
+pub struct TilePattern;
+
+fn stroke(points: Vec<Vec2>, closed: bool) -> shapes::Polygon {
+    shapes::Polygon { points, closed }
+}
+
+/// Icon drawn over a tile of the given state so it can be told apart without colour
+pub fn spawn_pattern(parent: &mut ChildBuilder, state: State, style: &TileStyle) {
+    if !style.patterns {
+        return;
+    }
+    let shapes = match state {
+        // a building with a door
+        State::Active => vec![
+            stroke(
+                vec![
+                    Vec2::new(-25., -35.),
+                    Vec2::new(-25., 35.),
+                    Vec2::new(25., 35.),
+                    Vec2::new(25., -35.),
+                ],
+                true,
+            ),
+            stroke(
+                vec![
+                    Vec2::new(-8., -35.),
+                    Vec2::new(-8., -15.),
+                    Vec2::new(8., -15.),
+                    Vec2::new(8., -35.),
+                ],
+                false,
+            ),
+        ],
+        // a cup with a handle
+        State::BreakShop => vec![
+            stroke(
+                vec![
+                    Vec2::new(-25., 25.),
+                    Vec2::new(-20., -25.),
+                    Vec2::new(15., -25.),
+                    Vec2::new(20., 25.),
+                ],
+                true,
+            ),
+            stroke(
+                vec![
+                    Vec2::new(18., 15.),
+                    Vec2::new(35., 12.),
+                    Vec2::new(33., -5.),
+                    Vec2::new(16., -8.),
+                ],
+                false,
+            ),
+        ],
+        // a cross
+        State::Obstacle => vec![
+            stroke(vec![Vec2::new(-30., -30.), Vec2::new(30., 30.)], false),
+            stroke(vec![Vec2::new(-30., 30.), Vec2::new(30., -30.)], false),
+        ],
+        State::Inactive => vec![],
+    };
+    let color = style.palette.pattern_color(state);
+    for shape in shapes {
+        parent
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                ShapeColors::new(color),
+                DrawMode::Stroke(StrokeOptions::default().with_line_width(PATTERN_WIDTH)),
+                Transform::from_xyz(0., 0., PATTERN_Z),
+            ))
+            .insert(TilePattern);
+    }
+}
+
+fn change_style(mut style: ResMut<TileStyle>, mut actions: EventReader<ActionEvent>) {
+    let mut changed = false;
+    for ActionEvent(action) in actions.iter() {
+        match action {
+            Action::CyclePalette => style.palette = style.palette.next(),
+            Action::TogglePatterns => style.patterns = !style.patterns,
+            _ => continue,
+        }
+        changed = true;
+    }
+    if changed {
+        style.save();
+    }
+}
+
+pub struct PalettePlugin;
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TileStyle>()
+            .add_system(change_style.system());
+    }
+}
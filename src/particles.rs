@@ -0,0 +1,490 @@
+use std::f32::consts::PI;
+
+use bevy::{log, prelude::*, utils::HashMap};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    config::{self, parse_color, parse_floats, Section},
+    utils::time_k,
+};
+
+pub const UPGRADE: &str = "upgrade";
+pub const OVERWAIT: &str = "overwait";
+pub const COIN: &str = "coin";
+pub const RAIN: &str = "rain";
+pub const SNOW: &str = "snow";
+
+const PRESETS_PATH: &str = "particles.cfg";
+const VARIETY: usize = 50;
+const DEFAULT_BUDGET: usize = 2000;
+// share of the budget that has to stay free for emitters to spawn their full amount
+const DEGRADE_FROM: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+enum SpawnShape {
+    Ring { min: f32, max: f32 },
+    Box { half: Vec2 },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VelocityKind {
+    Radial { min: f32, max: f32 },
+    Range { min: Vec2, max: Vec2 },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AccelerationKind {
+    Drag(f32),
+    Constant(Vec2),
+}
+
+#[derive(Debug, Clone)]
+struct EmitterPreset {
+    amount: u32,
+    amount_variance: f32,
+    duration: f32,
+    lifetime: f32,
+    decay: f32,
+    size: f32,
+    color_from: Color,
+    color_to: Color,
+    shape: SpawnShape,
+    velocity: VelocityKind,
+    acceleration: AccelerationKind,
+    offset: Vec3,
+    max_emitters: Option<u32>,
+}
+
+impl Default for EmitterPreset {
+    fn default() -> Self {
+        Self {
+            amount: 30,
+            amount_variance: 0.2,
+            duration: 2.,
+            lifetime: 100.,
+            decay: 3.,
+            size: 15.,
+            color_from: Color::WHITE,
+            color_to: Color::WHITE,
+            shape: SpawnShape::Box { half: Vec2::ZERO },
+            velocity: VelocityKind::Range {
+                min: Vec2::ZERO,
+                max: Vec2::ZERO,
+            },
+            acceleration: AccelerationKind::Constant(Vec2::ZERO),
+            offset: Vec3::ZERO,
+            max_emitters: None,
+        }
+    }
+}
+
+fn parse_shape(value: &str) -> Option<SpawnShape> {
+    let (kind, args) = value.split_once(' ')?;
+    match (kind, &parse_floats(args)[..]) {
+        ("ring", &[a, b]) => Some(SpawnShape::Ring {
+            min: a.min(b),
+            max: a.max(b),
+        }),
+        ("box", &[x, y]) => Some(SpawnShape::Box {
+            half: Vec2::new(x, y).abs(),
+        }),
+        _ => None,
+    }
+}
+
+fn parse_velocity(value: &str) -> Option<VelocityKind> {
+    let (kind, args) = value.split_once(' ')?;
+    match (kind, &parse_floats(args)[..]) {
+        ("radial", &[a, b]) => Some(VelocityKind::Radial {
+            min: a.min(b),
+            max: a.max(b),
+        }),
+        ("range", &[ax, ay, bx, by]) => {
+            let (a, b) = (Vec2::new(ax, ay), Vec2::new(bx, by));
+            Some(VelocityKind::Range {
+                min: a.min(b),
+                max: a.max(b),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn parse_acceleration(value: &str) -> Option<AccelerationKind> {
+    let (kind, args) = value.split_once(' ')?;
+    match (kind, &parse_floats(args)[..]) {
+        ("drag", &[k]) => Some(AccelerationKind::Drag(k)),
+        ("constant", &[x, y]) => Some(AccelerationKind::Constant(Vec2::new(x, y))),
+        _ => None,
+    }
+}
+
+fn parse_vec2(value: &str) -> Option<Vec2> {
+    match parse_floats(value)[..] {
+        [x, y] => Some(Vec2::new(x, y)),
+        _ => None,
+    }
+}
+
+fn parse_entry<T>(section: &Section, key: &str, parse: fn(&str) -> Option<T>) -> Option<T> {
+    let value = section.get(key)?;
+    let parsed = parse(value);
+    if parsed.is_none() {
+        log::warn!(
+            "Invalid `{}` for particles [{}]: {}",
+            key,
+            section.name,
+            value
+        );
+    }
+    parsed
+}
+
+impl EmitterPreset {
+    fn from_section(section: &Section) -> Self {
+        let mut preset = Self::default();
+        if let Some(x) = section.get_parsed("amount") {
+            preset.amount = x;
+        }
+        if let Some(x) = section.get_parsed::<f32>("amount_variance") {
+            // gen_range panics on reversed bounds
+            preset.amount_variance = x.abs().min(1.);
+        }
+        if let Some(x) = section.get_parsed("duration") {
+            preset.duration = x;
+        }
+        if let Some(x) = section.get_parsed("lifetime") {
+            preset.lifetime = x;
+        }
+        if let Some(x) = section.get_parsed("decay") {
+            preset.decay = x;
+        }
+        if let Some(x) = section.get_parsed("size") {
+            preset.size = x;
+        }
+        if let Some(x) = section.get_parsed("max_emitters") {
+            preset.max_emitters = Some(x);
+        }
+        if let Some(x) = parse_entry(section, "color_from", parse_color) {
+            preset.color_from = x;
+        }
+        if let Some(x) = parse_entry(section, "color_to", parse_color) {
+            preset.color_to = x;
+        }
+        if let Some(x) = parse_entry(section, "shape", parse_shape) {
+            preset.shape = x;
+        }
+        if let Some(x) = parse_entry(section, "velocity", parse_velocity) {
+            preset.velocity = x;
+        }
+        if let Some(x) = parse_entry(section, "acceleration", parse_acceleration) {
+            preset.acceleration = x;
+        }
+        if let Some(x) = parse_entry(section, "offset", parse_vec2) {
+            preset.offset = x.extend(0.);
+        }
+        preset
+    }
+
+    fn start_location<R: Rng>(&self, rng: &mut R) -> Vec3 {
+        match self.shape {
+            SpawnShape::Ring { min, max } => {
+                let radius = rng.gen_range(min..=max);
+                let angle = rng.gen_range(0_f32..2_f32 * PI);
+                // rotating (0, radius)
+                Vec3::new(-angle.sin() * radius, -angle.cos() * radius, 0.)
+            }
+            SpawnShape::Box { half } => Vec3::new(
+                rng.gen_range(-half.x..=half.x),
+                rng.gen_range(-half.y..=half.y),
+                0.,
+            ),
+        }
+    }
+
+    fn start_velocity<R: Rng>(&self, rng: &mut R, location: Vec3) -> Vec3 {
+        match self.velocity {
+            VelocityKind::Radial { min, max } => {
+                location.normalize_or_zero() * rng.gen_range(min..=max)
+            }
+            VelocityKind::Range { min, max } => Vec3::new(
+                rng.gen_range(min.x..=max.x),
+                rng.gen_range(min.y..=max.y),
+                0.,
+            ),
+        }
+    }
+
+    fn acceleration(&self, velocity: Vec3) -> Vec3 {
+        match self.acceleration {
+            AccelerationKind::Drag(k) => -velocity * k,
+            AccelerationKind::Constant(a) => a.extend(0.),
+        }
+    }
+}
+
+struct Preset {
+    params: EmitterPreset,
+    materials: Vec<Handle<ColorMaterial>>,
+    current_emitters: u32,
+}
+
+struct ParticlePresets {
+    presets: HashMap<String, Preset>,
+    budget: usize,
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color::rgba(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t,
+        from.a() + (to.a() - from.a()) * t,
+    )
+}
+
+impl FromWorld for ParticlePresets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut color_materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let asset_server = world.get_resource_mut::<AssetServer>().unwrap();
+        let texture = asset_server.load("particle.png");
+        let sections = config::load_asset(PRESETS_PATH).unwrap_or_else(|| {
+            log::warn!("Cannot find {}, particles are disabled", PRESETS_PATH);
+            vec![]
+        });
+        let budget = sections
+            .iter()
+            .filter(|s| s.name.is_empty())
+            .find_map(|s| s.get_parsed("budget"))
+            .unwrap_or(DEFAULT_BUDGET);
+        let mut presets = HashMap::default();
+        for section in sections.iter().filter(|s| !s.name.is_empty()) {
+            let params = EmitterPreset::from_section(section);
+            let materials = (0..VARIETY)
+                .map(|i| {
+                    let mut material: ColorMaterial = texture.clone().into();
+                    let t = i as f32 / (VARIETY - 1) as f32;
+                    material.color = lerp_color(params.color_from, params.color_to, t);
+                    color_materials.add(material)
+                })
+                .collect();
+            let preset = Preset {
+                params,
+                materials,
+                current_emitters: 0,
+            };
+            presets.insert(section.name.clone(), preset);
+        }
+        ParticlePresets { presets, budget }
+    }
+}
+
+// Dead particles are hidden and kept around to be reused by later emitters
+#[derive(Default)]
+struct ParticlePool {
+    free: Vec<Entity>,
+    live: usize,
+}
+
+impl ParticlePool {
+    fn degraded_amount(&self, amount: usize, budget: usize) -> usize {
+        if budget == 0 {
+            return 0;
+        }
+        let free = budget.saturating_sub(self.live);
+        let free_share = free as f32 / budget as f32;
+        let amount = if free_share < DEGRADE_FROM {
+            (amount as f32 * free_share / DEGRADE_FROM) as usize
+        } else {
+            amount
+        };
+        amount.min(free)
+    }
+}
+
+pub struct StartEmitter(pub &'static str, pub Vec3);
+
+struct Emitter {
+    preset: &'static str,
+    duration: Timer,
+}
+
+struct Particle;
+struct Lifetime {
+    left: f32,
+    max: f32,
+    decay: f32,
+    size: f32,
+}
+struct Velocity(Vec3);
+struct Acceleration(Vec3);
+struct Alive(bool);
+
+fn create_emitter(
+    mut event_reader: EventReader<StartEmitter>,
+    mut commands: Commands,
+    mut presets: ResMut<ParticlePresets>,
+    mut pool: ResMut<ParticlePool>,
+) {
+    let budget = presets.budget;
+    for StartEmitter(name, translation) in event_reader.iter() {
+        let preset = if let Some(x) = presets.presets.get_mut(*name) {
+            x
+        } else {
+            log::warn!("Unknown particle preset {}", name);
+            continue;
+        };
+        if let Some(max) = preset.params.max_emitters {
+            if preset.current_emitters >= max {
+                continue;
+            }
+        }
+        preset.current_emitters += 1;
+        let params = &preset.params;
+        let materials = &preset.materials;
+        let origin = *translation + params.offset;
+        commands
+            .spawn()
+            .insert(Transform::from_translation(origin))
+            .insert(GlobalTransform::default())
+            .insert(Emitter {
+                preset: *name,
+                duration: Timer::from_seconds(params.duration, false),
+            });
+
+        let mut rng = thread_rng();
+        let variance = params.amount_variance;
+        let amount = params.amount as f32 * rng.gen_range((1. - variance)..=(1. + variance));
+        let amount = pool.degraded_amount(amount as usize, budget);
+        pool.live += amount;
+        for _ in 0..amount {
+            let material = materials[rng.gen_range(0..materials.len())].clone();
+            let trnsl = params.start_location(&mut rng);
+            let velocity = params.start_velocity(&mut rng, trnsl);
+            let transform = Transform::from_translation(origin + trnsl);
+            let mut ec = if let Some(entity) = pool.free.pop() {
+                let mut ec = commands.entity(entity);
+                ec.insert(transform)
+                    .insert(material)
+                    .insert(Sprite::new(Vec2::splat(params.size)))
+                    .insert(Visible {
+                        is_visible: true,
+                        is_transparent: true,
+                    });
+                ec
+            } else {
+                commands.spawn_bundle(SpriteBundle {
+                    sprite: Sprite::new(Vec2::splat(params.size)),
+                    material,
+                    transform,
+                    ..Default::default()
+                })
+            };
+            ec.insert(Particle)
+                .insert(Acceleration(params.acceleration(velocity)))
+                .insert(Velocity(velocity))
+                .insert(Alive(true))
+                .insert(Lifetime {
+                    left: params.lifetime,
+                    max: params.lifetime,
+                    decay: params.decay,
+                    size: params.size,
+                });
+        }
+    }
+}
+
+fn kill_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut query: Query<(Entity, &mut Lifetime, &mut Sprite, &mut Alive, &mut Visible)>,
+) {
+    let time_k = time_k(&time);
+    for (entity, mut lifetime, mut sprite, mut alive, mut visible) in query.iter_mut() {
+        if !alive.0 {
+            continue;
+        }
+        lifetime.left -= lifetime.decay * time_k;
+        let ratio = lifetime.left / lifetime.max;
+        sprite.size = Vec2::splat(lifetime.size * ratio);
+        if lifetime.left <= 0. {
+            alive.0 = false;
+            visible.is_visible = false;
+            pool.live -= 1;
+            pool.free.push(entity);
+        }
+    }
+}
+
+fn kill_emitter(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Emitter)>,
+    mut presets: ResMut<ParticlePresets>,
+) {
+    for (entity, mut emitter) in query.iter_mut() {
+        if emitter.duration.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            if let Some(preset) = presets.presets.get_mut(emitter.preset) {
+                preset.current_emitters -= 1;
+            }
+        }
+    }
+}
+
+fn update_pos(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Velocity, &Acceleration, &Alive), With<Particle>>,
+) {
+    let time_k = time_k(&time);
+    for (mut pos, mut vel, accel, is_alive) in query.iter_mut() {
+        if is_alive.0 {
+            vel.0 += accel.0 * time_k;
+            pos.translation += vel.0 * time_k;
+        }
+    }
+}
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(create_emitter.system())
+            .add_system(kill_emitter.system())
+            .add_system(kill_particles.system())
+            .add_system(update_pos.system())
+            .add_event::<StartEmitter>()
+            .init_resource::<ParticlePresets>()
+            .init_resource::<ParticlePool>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(live: usize) -> ParticlePool {
+        ParticlePool { free: vec![], live }
+    }
+
+    #[test]
+    fn full_amount_while_budget_is_mostly_free() {
+        assert_eq!(pool(0).degraded_amount(10, 100), 10);
+        assert_eq!(pool(50).degraded_amount(10, 100), 10);
+    }
+
+    #[test]
+    fn amount_shrinks_as_budget_fills_up() {
+        assert_eq!(pool(75).degraded_amount(10, 100), 5);
+        assert_eq!(pool(90).degraded_amount(10, 100), 2);
+    }
+
+    #[test]
+    fn never_exceeds_free_budget() {
+        assert_eq!(pool(98).degraded_amount(10, 100), 0);
+        assert_eq!(pool(0).degraded_amount(50, 20), 20);
+        assert_eq!(pool(120).degraded_amount(10, 100), 0);
+        assert_eq!(pool(0).degraded_amount(10, 0), 0);
+    }
+}
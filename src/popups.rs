@@ -0,0 +1,119 @@
+use bevy::{prelude::*, utils::HashMap};
+use hex2d::{Coordinate, Spacing};
+
+use crate::field::SIZE;
+
+const POPUP_LIFETIME_SECS: f32 = 1.5;
+// events landing on a tile while its popup is younger than this are added to it
+const AGGREGATE_SECS: f32 = 0.5;
+const RISE_PER_SEC: f32 = 40.;
+const GAIN_OFFSET: f32 = 40.;
+const LOSS_OFFSET: f32 = 0.;
+
+/// Money earned (positive) or lost (negative) on a tile
+pub struct MoneyPopupEvent(pub Coordinate, pub i32);
+
+struct MoneyPopup {
+    tile: Coordinate,
+    amount: i32,
+    age: f32,
+}
+
+impl MoneyPopup {
+    fn label(&self) -> String {
+        format!("{:+}", self.amount)
+    }
+}
+
+fn popup_color(amount: i32) -> Color {
+    if amount.is_negative() {
+        Color::CRIMSON
+    } else {
+        Color::GOLD
+    }
+}
+
+fn spawn_popups(
+    mut commands: Commands,
+    mut events: EventReader<MoneyPopupEvent>,
+    asset_server: Res<AssetServer>,
+    mut popups: Query<(&mut MoneyPopup, &mut Text)>,
+) {
+    let mut totals: HashMap<(Coordinate, bool), i32> = HashMap::default();
+    for &MoneyPopupEvent(tile, amount) in events.iter() {
+        if amount != 0 {
+            *totals.entry((tile, amount.is_negative())).or_insert(0) += amount;
+        }
+    }
+    for (mut popup, mut text) in popups.iter_mut() {
+        if popup.age > AGGREGATE_SECS {
+            continue;
+        }
+        let key = (popup.tile, popup.amount.is_negative());
+        if let Some(amount) = totals.remove(&key) {
+            popup.amount += amount;
+            text.sections[0].value = popup.label();
+        }
+    }
+
+    let style = TextStyle {
+        font: asset_server.load("FiraSans-Bold.ttf"),
+        font_size: 36.0,
+        color: Color::WHITE,
+    };
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    for ((tile, _), amount) in totals {
+        let popup = MoneyPopup {
+            tile,
+            amount,
+            age: 0.,
+        };
+        let style = TextStyle {
+            color: popup_color(amount),
+            ..style.clone()
+        };
+        let (x, y) = tile.to_pixel(Spacing::FlatTop(SIZE));
+        let offset = if amount.is_negative() {
+            LOSS_OFFSET
+        } else {
+            GAIN_OFFSET
+        };
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(popup.label(), style, alignment),
+                transform: Transform::from_xyz(x, y + offset, 0.95),
+                ..Default::default()
+            })
+            .insert(popup);
+    }
+}
+
+fn animate_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut MoneyPopup, &mut Text, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut popup, mut text, mut transform) in popups.iter_mut() {
+        popup.age += dt;
+        if popup.age >= POPUP_LIFETIME_SECS {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += RISE_PER_SEC * dt;
+        let alpha = 1. - popup.age / POPUP_LIFETIME_SECS;
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+pub struct PopupsPlugin;
+impl Plugin for PopupsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<MoneyPopupEvent>()
+            .add_system(spawn_popups.system())
+            .add_system(animate_popups.system());
+    }
+}
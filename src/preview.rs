@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use hex2d::{Coordinate, Spacing};
+
+use crate::{
+    calendar::Calendar,
+    daytime::{Daytime, NewHourEvent, TICKS_IN_HOUR},
+    difficulty::DifficultySettings,
+    field::{
+        build_hex_shape, demand, expected_ticks_between_workers, CoffeeShops, Map, SelectedHex,
+        SIZE, WAIT_TICKS_AFTER_SERVING,
+    },
+    reputation::Reputation,
+    weather::CurrentWeather,
+    workers::WorkerKinds,
+};
+
+const MAX_LISTED_OFFICES: usize = 5;
+
+struct PreviewOutline;
+struct PreviewCard;
+
+struct PreviewMaterials {
+    card: Handle<ColorMaterial>,
+}
+
+impl FromWorld for PreviewMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        PreviewMaterials {
+            card: materials.add(Color::rgb_u8(230, 245, 255).into()),
+        }
+    }
+}
+
+// Offices that would switch to a shop placed at `target`, with their new path lengths
+fn switching_offices(map: &Map, target: Coordinate) -> Vec<(Coordinate, usize)> {
+    let mut offices: Vec<_> = map
+        .offices()
+        .filter_map(|office| {
+            let path = map.find_path(office, |c| c == target || map.is_coffee(c))?;
+            if path.first() == Some(&target) {
+                Some((office, path.len()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    offices.sort_by_key(|(_, len)| *len);
+    offices
+}
+
+fn preview_lines(
+    offices: &[(Coordinate, usize)],
+    shops: &CoffeeShops,
+    difficulty: &DifficultySettings,
+    kinds: &WorkerKinds,
+    reputation: &Reputation,
+    demand: f32,
+) -> Vec<String> {
+    let mut lines = vec![];
+    if shops.0 >= shops.1 {
+        lines.push("Shop limit reached".to_string());
+    }
+    if offices.is_empty() {
+        lines.push("No offices would use this shop".to_string());
+        return lines;
+    }
+    lines.push(format!("{} offices would switch here", offices.len()));
+    for (_, len) in offices.iter().take(MAX_LISTED_OFFICES) {
+        lines.push(format!(
+            "  {} tiles, +{:.1} per worker",
+            len,
+            kinds.expected_payout(*len, reputation)
+        ));
+    }
+    if offices.len() > MAX_LISTED_OFFICES {
+        lines.push(format!(
+            "  ...and {} more",
+            offices.len() - MAX_LISTED_OFFICES
+        ));
+    }
+    let workers_per_hour = offices.len() as f32
+        / expected_ticks_between_workers(difficulty.base_chance_to_spawn_worker, demand)
+        * TICKS_IN_HOUR as f32;
+    let capacity = TICKS_IN_HOUR / (WAIT_TICKS_AFTER_SERVING + 1);
+    lines.push(format!(
+        "Expected load: {:.1}/h of {}/h",
+        workers_per_hour, capacity
+    ));
+    lines
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_preview(
+    mut commands: Commands,
+    selected: Res<Option<SelectedHex>>,
+    map: Res<Map>,
+    shops: Res<CoffeeShops>,
+    difficulty: Res<DifficultySettings>,
+    kinds: Res<WorkerKinds>,
+    reputation: Res<Reputation>,
+    calendar: Res<Calendar>,
+    daytime: Res<Daytime>,
+    weather: Res<CurrentWeather>,
+    mut hours: EventReader<NewHourEvent>,
+    materials: Res<PreviewMaterials>,
+    asset_server: Res<AssetServer>,
+    outlines: Query<Entity, With<PreviewOutline>>,
+    cards: Query<Entity, With<PreviewCard>>,
+) {
+    // calendar demand changes on the hour
+    let new_hour = hours.iter().count() > 0;
+    if !selected.is_changed()
+        && !map.is_changed()
+        && !shops.is_changed()
+        && !reputation.is_changed()
+        && !weather.is_changed()
+        && !new_hour
+    {
+        return;
+    }
+    for entity in outlines.iter().chain(cards.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    let target = if let Some(x) = selected.as_ref() {
+        x.coordinate
+    } else {
+        return;
+    };
+    let upgradeable = map.get(target).map(|s| s.is_upgradeable()).unwrap_or(false);
+    if !upgradeable {
+        return;
+    }
+    let offices = switching_offices(&map, target);
+    for (office, _) in offices.iter() {
+        let (x, y) = office.to_pixel(Spacing::FlatTop(SIZE));
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &build_hex_shape(),
+                ShapeColors::new(Color::YELLOW),
+                DrawMode::Stroke(StrokeOptions::default().with_line_width(5.0)),
+                Transform::from_xyz(x, y, 0.4),
+            ))
+            .insert(PreviewOutline);
+    }
+
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    let style = TextStyle {
+        font,
+        font_size: 24.0,
+        color: Color::BLACK,
+    };
+    let demand = demand(&reputation, &calendar, &daytime, &weather);
+    let lines = preview_lines(&offices, &shops, &difficulty, &kinds, &reputation, demand);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.)),
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.card.clone(),
+            ..Default::default()
+        })
+        .insert(PreviewCard)
+        .with_children(|ec| {
+            for line in lines {
+                ec.spawn_bundle(TextBundle {
+                    text: Text::with_section(line, style.clone(), Default::default()),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+pub struct PreviewPlugin;
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PreviewMaterials>()
+            .add_system(update_preview.system());
+    }
+}
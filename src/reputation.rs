@@ -0,0 +1,76 @@
+use std::process;
+
+use bevy::{log, prelude::*};
+
+use crate::{
+    daytime::NewHourEvent,
+    workers::{OverwaitEvent, ServedEvent},
+};
+
+const MAX_REPUTATION: f32 = 100.;
+const START_REPUTATION: f32 = 50.;
+const SERVE_GAIN: f32 = 2.;
+// workers served with less than this share of patience left lower the reputation
+const SLOW_SERVICE_SHARE: f32 = 0.25;
+const OVERWAIT_LOSS: f32 = 5.;
+pub const LOW_REPUTATION: f32 = 15.;
+const GAME_OVER_AFTER_LOW_HOURS: u32 = 6;
+
+pub struct Reputation(pub f32);
+
+impl Default for Reputation {
+    fn default() -> Self {
+        Self(START_REPUTATION)
+    }
+}
+
+impl Reputation {
+    /// Scales office spawn chances and worker payouts, 0.5 at zero, 1.5 at max
+    pub fn multiplier(&self) -> f32 {
+        0.5 + self.0 / MAX_REPUTATION
+    }
+
+    fn change(&mut self, delta: f32) {
+        self.0 = (self.0 + delta).max(0.).min(MAX_REPUTATION);
+    }
+}
+
+fn change_reputation(
+    mut reputation: ResMut<Reputation>,
+    mut served: EventReader<ServedEvent>,
+    mut overwaits: EventReader<OverwaitEvent>,
+) {
+    for ServedEvent(_, patience_left) in served.iter() {
+        reputation.change(SERVE_GAIN * (patience_left - SLOW_SERVICE_SHARE));
+    }
+    for _ in overwaits.iter() {
+        reputation.change(-OVERWAIT_LOSS);
+    }
+}
+
+fn check_game_over(
+    reputation: Res<Reputation>,
+    mut hours: EventReader<NewHourEvent>,
+    mut low_hours: Local<u32>,
+) {
+    for _ in hours.iter() {
+        if reputation.0 < LOW_REPUTATION {
+            *low_hours += 1;
+        } else {
+            *low_hours = 0;
+        }
+        if *low_hours >= GAME_OVER_AFTER_LOW_HOURS {
+            log::info!("Game over! Nobody wants your coffee anymore");
+            process::exit(0);
+        }
+    }
+}
+
+pub struct ReputationPlugin;
+impl Plugin for ReputationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Reputation>()
+            .add_system(change_reputation.system())
+            .add_system(check_game_over.system());
+    }
+}
@@ -1,27 +1,73 @@
-use std::process;
-
 use bevy::{
     core::FixedTimestep,
     diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
-    log,
     prelude::*,
+    utils::HashMap,
     window::WindowResized,
 };
 
+use hex2d::Coordinate;
+
 use crate::{
-    daytime::Daytime,
-    field::{CoffeeShops, Map, NextRingTimer, SIZE},
+    calendar::Calendar,
+    daytime::{Daytime, GameSpeed},
+    field::{CoffeeShops, ExpansionTimer, Map},
+    input::{Action, ActionEvent},
+    reputation::Reputation,
+    weather::CurrentWeather,
     MainCamera,
 };
 
 struct FpsCounter;
-struct NextRingCounter;
+struct ExpansionCounter;
 struct MoneyTextCounter;
+struct ReputationTextCounter;
+struct WeatherTextCounter;
 struct TimeTextCounter;
 struct CoffeeShopsCounter;
+struct LedgerCounter(MoneyCategory);
+
+pub struct Money(pub u32);
+pub struct ChangeMoneyEvent(pub i32, pub MoneyCategory);
+
+/// Payments the wallet could not cover, settled from the next income
+#[derive(Default)]
+pub struct Debt(pub u32);
+
+/// A payment could not be covered, with the total now owed
+pub struct DebtEvent(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoneyCategory {
+    Service,
+    Fee,
+    Upkeep,
+    Purchase,
+    Reward,
+}
 
-pub struct Money(u32);
-pub struct ChangeMoneyEvent(pub i32);
+const MONEY_CATEGORIES: [MoneyCategory; 5] = [
+    MoneyCategory::Service,
+    MoneyCategory::Fee,
+    MoneyCategory::Upkeep,
+    MoneyCategory::Purchase,
+    MoneyCategory::Reward,
+];
+
+impl MoneyCategory {
+    fn label(&self) -> &'static str {
+        match self {
+            MoneyCategory::Service => "Service",
+            MoneyCategory::Fee => "Fees",
+            MoneyCategory::Upkeep => "Upkeep",
+            MoneyCategory::Purchase => "Purchases",
+            MoneyCategory::Reward => "Rewards",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Ledger(HashMap<MoneyCategory, i32>);
 
 impl Default for Money {
     fn default() -> Self {
@@ -31,7 +77,8 @@ impl Default for Money {
 
 pub struct UpgradeTileEvent;
 
-pub struct GeneratedNextRing(pub u32);
+/// The map grew in the named sector, around the given tile
+pub struct MapExpandedEvent(pub &'static str, pub Coordinate);
 
 pub struct UiPlugin;
 
@@ -44,10 +91,50 @@ fn fps_change_text(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, Wi
     }
 }
 
-fn money_change_text(money: Res<Money>, mut query: Query<&mut Text, With<MoneyTextCounter>>) {
-    if money.is_changed() {
+fn money_change_text(
+    money: Res<Money>,
+    debt: Res<Debt>,
+    mut query: Query<&mut Text, With<MoneyTextCounter>>,
+) {
+    if money.is_changed() || debt.is_changed() {
+        let value = if debt.0 > 0 {
+            format!("Money: {} (owed {})", money.0, debt.0)
+        } else {
+            format!("Money: {}", money.0)
+        };
+        for mut text in query.iter_mut() {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn reputation_change_text(
+    reputation: Res<Reputation>,
+    mut query: Query<&mut Text, With<ReputationTextCounter>>,
+) {
+    if reputation.is_changed() {
         for mut text in query.iter_mut() {
-            text.sections[0].value = format!("Money: {}", money.0 as i64);
+            text.sections[0].value = format!("Reputation: {}", reputation.0.round() as i64);
+        }
+    }
+}
+
+fn weather_change_text(
+    weather: Res<CurrentWeather>,
+    mut query: Query<&mut Text, With<WeatherTextCounter>>,
+) {
+    if weather.is_changed() {
+        for mut text in query.iter_mut() {
+            text.sections[0].value = format!("Weather: {}", weather.0.name());
+        }
+    }
+}
+
+fn ledger_change_text(ledger: Res<Ledger>, mut query: Query<(&mut Text, &LedgerCounter)>) {
+    if ledger.is_changed() {
+        for (mut text, LedgerCounter(category)) in query.iter_mut() {
+            let total = ledger.0.get(category).copied().unwrap_or(0);
+            text.sections[0].value = format!("{}: {:+}", category.label(), total);
         }
     }
 }
@@ -63,19 +150,33 @@ fn shops_change_text(
     }
 }
 
-fn next_ring_change_text(
-    timer: Res<NextRingTimer>,
-    mut query: Query<&mut Text, With<NextRingCounter>>,
+fn expansion_change_text(
+    timer: Res<ExpansionTimer>,
+    mut query: Query<&mut Text, With<ExpansionCounter>>,
 ) {
     let left = timer.0.percent_left() * 100.;
     for mut text in query.iter_mut() {
-        text.sections[0].value = format!("Until next ring: {}%", left as i64);
+        text.sections[0].value = format!("Until next expansion: {}%", left as i64);
     }
 }
 
-fn daytime_change_text(daytime: Res<Daytime>, mut query: Query<&mut Text, With<TimeTextCounter>>) {
+fn daytime_change_text(
+    daytime: Res<Daytime>,
+    calendar: Res<Calendar>,
+    speed: Res<GameSpeed>,
+    mut query: Query<&mut Text, With<TimeTextCounter>>,
+) {
+    let mut value = daytime.to_string();
+    if let Some(special) = calendar.active(&daytime) {
+        value.push_str(&format!("\n{}", special));
+    }
+    if speed.paused {
+        value.push_str(" (paused)");
+    } else if speed.multiplier() > 1 {
+        value.push_str(&format!(" (x{})", speed.multiplier()));
+    }
     for mut text in query.iter_mut() {
-        text.sections[0].value = daytime.to_string();
+        text.sections[0].value = value.clone();
     }
 }
 
@@ -137,6 +238,18 @@ fn setup(
             horizontal: HorizontalAlign::Left,
         },
     );
+    let reputation_text = Text::with_section(
+        "Reputation: 50".to_string(),
+        TextStyle {
+            font: font_handle.clone(),
+            font_size: 30.0,
+            color: Color::BLACK,
+        },
+        TextAlignment {
+            vertical: VerticalAlign::Top,
+            horizontal: HorizontalAlign::Left,
+        },
+    );
     let time_text = Text::with_section(
         "Time: 00:00".to_string(),
         TextStyle {
@@ -149,8 +262,20 @@ fn setup(
             horizontal: HorizontalAlign::Left,
         },
     );
+    let weather_text = Text::with_section(
+        "Weather: Clear".to_string(),
+        TextStyle {
+            font: font_handle.clone(),
+            font_size: 30.0,
+            color: Color::BLACK,
+        },
+        TextAlignment {
+            vertical: VerticalAlign::Top,
+            horizontal: HorizontalAlign::Left,
+        },
+    );
     let text = Text::with_section(
-        "Until next ring: ".to_string(),
+        "Until next expansion: ".to_string(),
         TextStyle {
             font: font_handle.clone(),
             font_size: 30.0,
@@ -164,7 +289,7 @@ fn setup(
     let shops_text = Text::with_section(
         "Coffee shops: 1/1".to_string(),
         TextStyle {
-            font: font_handle,
+            font: font_handle.clone(),
             font_size: 30.0,
             color: Color::BLACK,
         },
@@ -173,6 +298,18 @@ fn setup(
             horizontal: HorizontalAlign::Left,
         },
     );
+    let ledger_text = Text::with_section(
+        "".to_string(),
+        TextStyle {
+            font: font_handle,
+            font_size: 24.0,
+            color: Color::BLACK,
+        },
+        TextAlignment {
+            vertical: VerticalAlign::Top,
+            horizontal: HorizontalAlign::Left,
+        },
+    );
     let card_material = color_materials.add(Color::rgb_u8(230, 245, 255).into());
     ui_cmds
         .spawn_bundle(NodeBundle {
@@ -227,75 +364,158 @@ fn setup(
                     text: text.clone(),
                     ..Default::default()
                 })
-                .insert(NextRingCounter);
+                .insert(ExpansionCounter);
                 ec.spawn_bundle(TextBundle {
                     text: money_text,
                     ..Default::default()
                 })
                 .insert(MoneyTextCounter);
+                ec.spawn_bundle(TextBundle {
+                    text: reputation_text,
+                    ..Default::default()
+                })
+                .insert(ReputationTextCounter);
                 ec.spawn_bundle(TextBundle {
                     text: time_text,
                     ..Default::default()
                 })
                 .insert(TimeTextCounter);
+                ec.spawn_bundle(TextBundle {
+                    text: weather_text,
+                    ..Default::default()
+                })
+                .insert(WeatherTextCounter);
                 ec.spawn_bundle(TextBundle {
                     text: shops_text,
                     ..Default::default()
                 })
                 .insert(CoffeeShopsCounter);
             });
+            // Finance section
+            ec.spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::ColumnReverse,
+                    size: Size {
+                        width: Val::Px(310.),
+                        height: Val::Undefined,
+                    },
+                    margin: Rect {
+                        top: Val::Px(10.),
+                        ..Default::default()
+                    },
+                    padding: Rect::all(Val::Px(10.)),
+                    align_items: AlignItems::FlexStart,
+                    ..Default::default()
+                },
+                material: card_material.clone(),
+                ..Default::default()
+            })
+            .with_children(|ec| {
+                for category in MONEY_CATEGORIES.iter() {
+                    ec.spawn_bundle(TextBundle {
+                        text: ledger_text.clone(),
+                        ..Default::default()
+                    })
+                    .insert(LedgerCounter(*category));
+                }
+            });
         });
 }
 
-fn change_money(mut money: ResMut<Money>, mut events: EventReader<ChangeMoneyEvent>) {
-    for &ChangeMoneyEvent(delta) in events.iter() {
+fn change_money(
+    mut money: ResMut<Money>,
+    mut debt: ResMut<Debt>,
+    mut ledger: ResMut<Ledger>,
+    mut events: EventReader<ChangeMoneyEvent>,
+    mut debt_events: EventWriter<DebtEvent>,
+) {
+    for &ChangeMoneyEvent(delta, category) in events.iter() {
+        *ledger.0.entry(category).or_insert(0) += delta;
+        let amount = delta.unsigned_abs();
         if delta.is_negative() {
-            let delta = delta.abs() as u32;
-            let res = money.0.checked_sub(delta);
-            if let Some(res) = res {
+            if let Some(res) = money.0.checked_sub(amount) {
                 money.0 = res;
-            } else {
-                log::info!("Game over!");
-                process::exit(0);
+                continue;
+            }
+            // whatever the wallet cannot cover waits for income instead of ending the run
+            let was_owed = debt.0;
+            debt.0 += amount - money.0;
+            money.0 = 0;
+            if was_owed == 0 {
+                debt_events.send(DebtEvent(debt.0));
             }
+        } else if debt.0 > 0 {
+            let settled = amount.min(debt.0);
+            debt.0 -= settled;
+            money.0 += amount - settled;
         } else {
-            money.0 += delta as u32;
+            money.0 += amount;
         }
     }
 }
 
-fn keyboard_input(
-    keys: Res<Input<KeyCode>>,
-    mouse: Res<Input<MouseButton>>,
+fn place_tile(
+    mut actions: EventReader<ActionEvent>,
     mut events: EventWriter<UpgradeTileEvent>,
+    buttons: Query<&Interaction, With<Button>>,
+    speed: Res<GameSpeed>,
 ) {
-    if keys.just_pressed(KeyCode::U) || mouse.just_pressed(MouseButton::Left) {
-        events.send(UpgradeTileEvent);
+    // clicks on UI buttons should not place shops under them
+    let over_button = buttons.iter().any(|i| *i != Interaction::None);
+    for ActionEvent(action) in actions.iter() {
+        if *action == Action::Place && !over_button && speed.started {
+            events.send(UpgradeTileEvent);
+        }
     }
 }
 
-fn calc_scale_vec(rings: u32, wnd_height: f32) -> Vec3 {
-    let total_height = SIZE * 3_f32.sqrt() * (1 + rings * 2) as f32;
-    let scale = total_height / wnd_height;
+fn calc_scale_vec(size: Vec2, wnd_size: Vec2) -> Vec3 {
+    let scale = (size / wnd_size).max_element();
     let scale = scale.max(1.);
     Vec3::new(scale, scale, 1.)
 }
 
-fn set_scale(query: &mut Query<&mut Transform, With<MainCamera>>, windows: &Windows, rings: u32) {
+/// Where the last fit left the camera, so a pan by the player can be told apart
+#[derive(Default)]
+pub struct CameraFit(pub Vec2);
+
+/// Centers the camera on the map and zooms out until all of it is visible
+pub fn fit_camera(transform: &mut Transform, map: &Map, wnd: &Window, fit: &mut CameraFit) {
+    let (min, max) = map.bounds();
+    let center = (min + max) / 2.;
+    transform.translation.x = center.x;
+    transform.translation.y = center.y;
+    transform.scale = calc_scale_vec(max - min, Vec2::new(wnd.width(), wnd.height()));
+    fit.0 = center;
+}
+
+// Refits the camera, unless the player has moved it since the last fit
+fn set_scale(
+    query: &mut Query<&mut Transform, With<MainCamera>>,
+    windows: &Windows,
+    map: &Map,
+    fit: &mut CameraFit,
+) {
     let wnd = windows.get_primary().unwrap();
-    for mut proj in query.iter_mut() {
-        // println!("{:?}", proj.scale);
-        proj.scale = calc_scale_vec(rings, wnd.height());
+    for mut transform in query.iter_mut() {
+        if transform.translation.truncate() == fit.0 {
+            fit_camera(&mut transform, map, wnd, fit);
+        } else {
+            let (min, max) = map.bounds();
+            transform.scale = calc_scale_vec(max - min, Vec2::new(wnd.width(), wnd.height()));
+        }
     }
 }
 
 fn change_camera_scale(
     mut query: Query<&mut Transform, With<MainCamera>>,
-    mut events: EventReader<GeneratedNextRing>,
+    mut events: EventReader<MapExpandedEvent>,
     windows: Res<Windows>,
+    map: Res<Map>,
+    mut fit: ResMut<CameraFit>,
 ) {
-    for &GeneratedNextRing(rings) in events.iter() {
-        set_scale(&mut query, &windows, rings);
+    for _ in events.iter() {
+        set_scale(&mut query, &windows, &map, &mut fit);
     }
 }
 
@@ -304,10 +524,10 @@ fn change_camera_scale_from_resize(
     mut events: EventReader<WindowResized>,
     windows: Res<Windows>,
     map: Res<Map>,
+    mut fit: ResMut<CameraFit>,
 ) {
     for _ in events.iter() {
-        let rings = map.generated_rings;
-        set_scale(&mut query, &windows, rings);
+        set_scale(&mut query, &windows, &map, &mut fit);
     }
 }
 
@@ -318,18 +538,25 @@ impl Plugin for UiPlugin {
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::steps_per_second(16.))
                     .with_system(fps_change_text.system())
-                    .with_system(next_ring_change_text.system())
+                    .with_system(expansion_change_text.system())
                     .with_system(money_change_text.system())
+                    .with_system(reputation_change_text.system())
                     .with_system(daytime_change_text.system())
-                    .with_system(shops_change_text.system()),
+                    .with_system(weather_change_text.system())
+                    .with_system(shops_change_text.system())
+                    .with_system(ledger_change_text.system()),
             )
             .add_system(change_camera_scale.system())
             .add_system(change_camera_scale_from_resize.system())
             .add_system(change_money.system())
-            .add_system(keyboard_input.system())
+            .add_system(place_tile.system())
             .add_event::<ChangeMoneyEvent>()
             .add_event::<UpgradeTileEvent>()
-            .add_event::<GeneratedNextRing>()
-            .init_resource::<Money>();
+            .add_event::<MapExpandedEvent>()
+            .init_resource::<Money>()
+            .init_resource::<Debt>()
+            .init_resource::<CameraFit>()
+            .add_event::<DebtEvent>()
+            .init_resource::<Ledger>();
     }
 }
@@ -0,0 +1,179 @@
+use bevy::{core::FixedTimestep, prelude::*};
+use bevy_prototype_lyon::prelude::*;
+use hex2d::Spacing;
+use rand::{
+    distributions::WeightedIndex,
+    prelude::{Distribution, IteratorRandom},
+    thread_rng, Rng,
+};
+
+use crate::{
+    daytime::NewDayEvent,
+    field::{build_hex_shape, Map, State, SIZE},
+    particles::{self, StartEmitter},
+    MainCamera,
+};
+
+// share of empty tiles a snowy day blocks
+const SNOWED_SHARE: f32 = 0.15;
+const EMITS_PER_SECOND: f64 = 10.;
+const PARTICLES_Z: f32 = 0.97;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Heat,
+    Snow,
+}
+
+const WEATHER_WEIGHTS: [(Weather, u32); 4] = [
+    (Weather::Clear, 50),
+    (Weather::Rain, 20),
+    (Weather::Heat, 15),
+    (Weather::Snow, 15),
+];
+
+impl Weather {
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "Clear",
+            Weather::Rain => "Rain",
+            Weather::Heat => "Heat",
+            Weather::Snow => "Snow",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Weather::Clear => "clear skies",
+            Weather::Rain => "workers walk slower but want more coffee",
+            Weather::Heat => "fewer workers want hot coffee",
+            Weather::Snow => "some tiles are snowed in for the day",
+        }
+    }
+
+    /// Multiplier for worker walking speed
+    pub fn speed(self) -> f32 {
+        match self {
+            Weather::Rain => 0.75,
+            Weather::Snow => 0.85,
+            _ => 1.,
+        }
+    }
+
+    /// Multiplier for office spawn chances
+    pub fn demand(self) -> f32 {
+        match self {
+            Weather::Rain => 1.3,
+            Weather::Heat => 0.7,
+            _ => 1.,
+        }
+    }
+
+    fn particles(self) -> Option<&'static str> {
+        match self {
+            Weather::Rain => Some(particles::RAIN),
+            Weather::Snow => Some(particles::SNOW),
+            _ => None,
+        }
+    }
+}
+
+pub struct CurrentWeather(pub Weather);
+
+impl Default for CurrentWeather {
+    fn default() -> Self {
+        Self(Weather::Clear)
+    }
+}
+
+struct SnowItem;
+
+fn change_weather(
+    mut weather: ResMut<CurrentWeather>,
+    mut map: ResMut<Map>,
+    mut days: EventReader<NewDayEvent>,
+) {
+    if days.iter().count() == 0 {
+        return;
+    }
+    let mut rng = thread_rng();
+    let distr = WeightedIndex::new(WEATHER_WEIGHTS.iter().map(|item| item.1)).unwrap();
+    weather.0 = WEATHER_WEIGHTS[distr.sample(&mut rng)].0;
+    if !map.snowed.is_empty() {
+        map.snowed.clear();
+    }
+    if weather.0 == Weather::Snow {
+        let empty: Vec<_> = map
+            .tiles()
+            .filter(|(_, state)| *state == State::Inactive)
+            .map(|(c, _)| c)
+            .collect();
+        let amount = (empty.len() as f32 * SNOWED_SHARE).ceil() as usize;
+        map.snowed = empty
+            .into_iter()
+            .choose_multiple(&mut rng, amount)
+            .into_iter()
+            .collect();
+    }
+}
+
+fn draw_snow(mut commands: Commands, map: Res<Map>, items: Query<Entity, With<SnowItem>>) {
+    if !map.is_changed() {
+        return;
+    }
+    for entity in items.iter() {
+        commands.entity(entity).despawn();
+    }
+    for c in map.snowed.iter() {
+        let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &build_hex_shape(),
+                ShapeColors::new(Color::rgba(0.95, 0.97, 1., 0.8)),
+                DrawMode::Fill(FillOptions::default()),
+                Transform::from_xyz(x, y, 0.3),
+            ))
+            .insert(SnowItem);
+    }
+}
+
+// Rain and snow fall over whatever part of the field the camera shows
+fn emit_weather(
+    weather: Res<CurrentWeather>,
+    windows: Res<Windows>,
+    camera: Query<&Transform, With<MainCamera>>,
+    mut emitter_events: EventWriter<StartEmitter>,
+) {
+    let preset = if let Some(x) = weather.0.particles() {
+        x
+    } else {
+        return;
+    };
+    let camera = if let Ok(x) = camera.single() {
+        x
+    } else {
+        return;
+    };
+    let wnd = windows.get_primary().unwrap();
+    let half = Vec2::new(wnd.width(), wnd.height()) / 2. * camera.scale.truncate();
+    let mut rng = thread_rng();
+    let x = camera.translation.x + rng.gen_range(-half.x..=half.x);
+    let y = camera.translation.y + rng.gen_range(-half.y..=half.y);
+    emitter_events.send(StartEmitter(preset, Vec3::new(x, y, PARTICLES_Z)));
+}
+
+pub struct WeatherPlugin;
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CurrentWeather>()
+            .add_system(change_weather.system())
+            .add_system(draw_snow.system())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(EMITS_PER_SECOND))
+                    .with_system(emit_weather.system()),
+            );
+    }
+}
@@ -1,11 +1,21 @@
-use std::f32::consts::PI;
+use std::{cmp::Reverse, f32::consts::PI};
 
-use bevy::{log, prelude::*, sprite::TextureAtlas};
+use bevy::{log, prelude::*, utils::HashMap};
 use hex2d::{Coordinate, Spacing};
-use rand::{thread_rng, Rng};
+use rand::{distributions::WeightedIndex, prelude::Distribution, thread_rng, Rng};
 
 use crate::{
-    daytime::TickEvent, field::SIZE, overwait_particles::StartOverwaitEmitter, ui::ChangeMoneyEvent,
+    animation::{Cheering, WorkerAnimation, WorkerBody, WorkerSprites},
+    config::{self, Section},
+    daytime::{GameSpeed, TickEvent},
+    difficulty::DifficultySettings,
+    field::{Map, SIZE},
+    particles::{self, StartEmitter},
+    popups::MoneyPopupEvent,
+    reputation::Reputation,
+    ui::{ChangeMoneyEvent, MoneyCategory},
+    utils::time_k,
+    weather::CurrentWeather,
 };
 
 pub struct Worker {
@@ -14,32 +24,224 @@ pub struct Worker {
     pub path: Vec<Coordinate>,
     pub waited_for_coffee: bool,
     pub will_bring_money: u8,
+    pub kind: WorkerKind,
+    pub patience: u32,
+    speed: f32,
 }
 
-const FRAMES_PER_ONE_TILE: u32 = 64;
-pub struct MovingWorker(u32, Vec3);
+const KINDS_PATH: &str = "workers.cfg";
 
-const MAX_WAITING_TICKS: u32 = 50;
-const FEE_FOR_OVERWAIT: i32 = -1;
+// Ordered by serving priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WorkerKind {
+    Intern,
+    Manager,
+    Executive,
+}
+
+const WORKER_KINDS: [WorkerKind; 3] = [
+    WorkerKind::Intern,
+    WorkerKind::Manager,
+    WorkerKind::Executive,
+];
+
+impl WorkerKind {
+    fn key(self) -> &'static str {
+        match self {
+            WorkerKind::Intern => "intern",
+            WorkerKind::Manager => "manager",
+            WorkerKind::Executive => "executive",
+        }
+    }
+
+    fn head_color(self, rng: &mut impl Rng) -> Color {
+        match self {
+            WorkerKind::Executive => Color::rgb_u8(
+                rng.gen_range(150..200),
+                rng.gen_range(120..160),
+                rng.gen_range(20..60),
+            ),
+            _ => Color::rgb_u8(
+                rng.gen_range(0..150),
+                rng.gen_range(0..150),
+                rng.gen_range(0..150),
+            ),
+        }
+    }
+
+    fn body_color(self, rng: &mut impl Rng) -> Color {
+        match self {
+            WorkerKind::Intern => Color::rgb_u8(
+                rng.gen_range(120..230),
+                rng.gen_range(120..230),
+                rng.gen_range(120..230),
+            ),
+            WorkerKind::Manager => Color::rgb_u8(
+                rng.gen_range(50..200),
+                rng.gen_range(50..200),
+                rng.gen_range(50..200),
+            ),
+            WorkerKind::Executive => {
+                let grey = rng.gen_range(20..50);
+                Color::rgb_u8(grey, grey, grey + 10)
+            }
+        }
+    }
+}
+
+struct KindParams {
+    weight: u32,
+    patience: u32,
+    payout: f32,
+    speed: f32,
+    scale: f32,
+}
+
+impl Default for KindParams {
+    fn default() -> Self {
+        Self {
+            weight: 1,
+            patience: DEFAULT_PATIENCE,
+            payout: 1.,
+            speed: 1.,
+            scale: 1.,
+        }
+    }
+}
+
+impl KindParams {
+    fn from_section(section: &Section) -> Self {
+        let mut params = KindParams::default();
+        if let Some(x) = section.get_parsed("weight") {
+            params.weight = x;
+        }
+        if let Some(x) = section.get_parsed("patience") {
+            params.patience = x;
+        }
+        if let Some(x) = section.get_parsed("payout") {
+            params.payout = x;
+        }
+        if let Some(x) = section.get_parsed("speed") {
+            params.speed = x;
+        }
+        if let Some(x) = section.get_parsed("scale") {
+            params.scale = x;
+        }
+        params
+    }
+}
+
+pub struct WorkerKinds {
+    params: HashMap<WorkerKind, KindParams>,
+    mix: Option<WeightedIndex<u32>>,
+}
+
+impl Default for WorkerKinds {
+    fn default() -> Self {
+        let sections = config::load_asset(KINDS_PATH).unwrap_or_else(|| {
+            log::warn!("Cannot find {}, all workers are managers", KINDS_PATH);
+            vec![Section::new(WorkerKind::Manager.key())]
+        });
+        let mut params = HashMap::default();
+        for kind in WORKER_KINDS.iter() {
+            if let Some(section) = sections.iter().find(|s| s.name == kind.key()) {
+                params.insert(*kind, KindParams::from_section(section));
+            } else {
+                params.insert(
+                    *kind,
+                    KindParams {
+                        weight: 0,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        let mix = WeightedIndex::new(WORKER_KINDS.iter().map(|k| params[k].weight)).ok();
+        if mix.is_none() {
+            log::warn!("No worker kind can be spawned, falling back to managers");
+        }
+        WorkerKinds { params, mix }
+    }
+}
+
+impl WorkerKinds {
+    fn pick(&self, rng: &mut impl Rng) -> WorkerKind {
+        self.mix
+            .as_ref()
+            .map(|mix| WORKER_KINDS[mix.sample(rng)])
+            .unwrap_or(WorkerKind::Manager)
+    }
+
+    /// Money a worker of `kind` brings back from a shop `path_len` tiles away
+    pub fn payout(&self, kind: WorkerKind, path_len: usize, reputation: &Reputation) -> u8 {
+        let money = money_for_path(path_len) as f32 * self.params[&kind].payout;
+        (money * reputation.multiplier()).round() as u8
+    }
+
+    /// Average payout of the next worker sent along a path of `path_len` tiles
+    pub fn expected_payout(&self, path_len: usize, reputation: &Reputation) -> f32 {
+        let total: u32 = self.params.values().map(|p| p.weight).sum();
+        let multiplier = if total == 0 {
+            self.params[&WorkerKind::Manager].payout
+        } else {
+            self.params
+                .values()
+                .map(|p| p.weight as f32 * p.payout)
+                .sum::<f32>()
+                / total as f32
+        };
+        money_for_path(path_len) as f32 * multiplier * reputation.multiplier()
+    }
+}
+
+// measured in 1/60 s frames, see `utils::time_k`
+const FRAMES_PER_ONE_TILE: f32 = 64.;
+pub struct MovingWorker {
+    from: Vec3,
+    to: Vec3,
+    frames: f32,
+    target: Coordinate,
+}
+
+const DEFAULT_PATIENCE: u32 = 50;
 
-fn money_for_path(path_len: usize) -> u8 {
+pub fn money_for_path(path_len: usize) -> u8 {
     let y = (path_len as f32) * -0.5 + 2.5;
     y.ceil().max(0.) as u8
 }
 
 pub struct WaitingWorker(u32);
 impl WaitingWorker {
-    pub fn is_dead(&self) -> bool {
-        self.0 >= MAX_WAITING_TICKS
+    pub fn is_dead(&self, worker: &Worker) -> bool {
+        self.0 >= worker.patience
+    }
+
+    pub fn patience_left(&self, worker: &Worker) -> u32 {
+        worker.patience.saturating_sub(self.0)
     }
 }
 
-pub struct ReturningWorker;
+pub type ServeOrder = (Reverse<WorkerKind>, u32);
 
-struct WorkerAtlasResource {
-    atlas: Handle<TextureAtlas>,
+/// Shops serve the waiting worker with the smallest key first
+pub fn serve_order(worker: &Worker, waiting: &WaitingWorker) -> ServeOrder {
+    (Reverse(worker.kind), waiting.patience_left(worker))
 }
 
+const QUEUE_START_RADIUS: f32 = 12.;
+const QUEUE_SPACING: f32 = 22.;
+// keeps long queues on their own hex, same margin as `random_pos`
+const QUEUE_MAX_RADIUS: f32 = SIZE / 2. * 1.732 - 15.;
+// share of the remaining distance to its slot a waiting worker covers per 1/60 s
+const QUEUE_SHUFFLE_SPEED: f32 = 0.1;
+
+pub struct ReturningWorker;
+
+/// A worker at the given shop ran out of patience
+pub struct OverwaitEvent(pub Coordinate);
+/// A worker was served at the given shop with this share of patience left
+pub struct ServedEvent(pub Coordinate, pub f32);
+
 fn random_pos(c: &Coordinate) -> (f32, f32) {
     let mut rng = thread_rng();
     let (x, y) = c.to_pixel(Spacing::FlatTop(SIZE));
@@ -55,11 +257,15 @@ fn random_pos(c: &Coordinate) -> (f32, f32) {
 fn start_moving_worker(
     mut commands: Commands,
     mut query: Query<
-        (Entity, &mut Worker, &Transform),
-        (Without<MovingWorker>, Without<WaitingWorker>),
+        (Entity, &mut Worker, &mut Transform),
+        (
+            Without<MovingWorker>,
+            Without<WaitingWorker>,
+            Without<Cheering>,
+        ),
     >,
 ) {
-    for (entity, mut worker, transform) in query.iter_mut() {
+    for (entity, mut worker, mut transform) in query.iter_mut() {
         let mut ec = commands.entity(entity);
         if worker.path.is_empty() {
             if worker.waited_for_coffee {
@@ -72,21 +278,32 @@ fn start_moving_worker(
         }
         let next_c = worker.path.pop().unwrap();
         let (x, y) = random_pos(&next_c);
-        let next = Vec3::new(x, y, 0.);
-        let speed = (next - transform.translation) / FRAMES_PER_ONE_TILE as f32;
-        let moving = MovingWorker(0, speed);
+        let dx = x - transform.translation.x;
+        if dx.abs() > f32::EPSILON {
+            transform.scale.x = transform.scale.x.abs() * dx.signum();
+        }
+        let moving = MovingWorker {
+            from: transform.translation,
+            to: Vec3::new(x, y, transform.translation.z),
+            frames: 0.,
+            target: next_c,
+        };
         ec.insert(moving);
     }
 }
 
 fn move_worker(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &mut MovingWorker)>,
+    time: Res<Time>,
+    speed: Res<GameSpeed>,
+    weather: Res<CurrentWeather>,
+    mut query: Query<(Entity, &Worker, &mut Transform, &mut MovingWorker)>,
 ) {
-    for (entity, mut tr, mut mw) in query.iter_mut() {
-        tr.translation += mw.1;
-        mw.0 += 1;
-        if mw.0 == FRAMES_PER_ONE_TILE {
+    let frames = time_k(&time) * speed.multiplier() as f32 * weather.0.speed();
+    for (entity, worker, mut tr, mut mw) in query.iter_mut() {
+        mw.frames = (mw.frames + frames * worker.speed).min(FRAMES_PER_ONE_TILE);
+        tr.translation = mw.from.lerp(mw.to, mw.frames / FRAMES_PER_ONE_TILE);
+        if mw.frames >= FRAMES_PER_ONE_TILE {
             let mut ec = commands.entity(entity);
             ec.remove::<MovingWorker>();
             log::debug!("stopped moving");
@@ -94,64 +311,176 @@ fn move_worker(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn wait_worker(
     mut commands: Commands,
     mut ticks: EventReader<TickEvent>,
     mut money: EventWriter<ChangeMoneyEvent>,
-    mut query: Query<(Entity, &mut WaitingWorker, &Transform)>,
-    mut overwait_events: EventWriter<StartOverwaitEmitter>,
+    mut popups: EventWriter<MoneyPopupEvent>,
+    mut overwaits: EventWriter<OverwaitEvent>,
+    mut query: Query<(Entity, &Worker, &mut WaitingWorker, &Transform)>,
+    mut emitter_events: EventWriter<StartEmitter>,
+    difficulty: Res<DifficultySettings>,
 ) {
+    let fee = difficulty.fee_for_overwait;
     for _ in ticks.iter() {
-        for (entity, mut w, trns) in query.iter_mut() {
+        for (entity, worker, mut w, trns) in query.iter_mut() {
             w.0 += 1;
-            if w.is_dead() {
+            if w.is_dead(worker) {
                 commands.entity(entity).despawn_recursive();
-                money.send(ChangeMoneyEvent(FEE_FOR_OVERWAIT));
-                overwait_events.send(StartOverwaitEmitter(trns.translation))
+                money.send(ChangeMoneyEvent(fee, MoneyCategory::Fee));
+                popups.send(MoneyPopupEvent(worker.coffee, fee));
+                overwaits.send(OverwaitEvent(worker.coffee));
+                let translation = Vec3::new(trns.translation.x, trns.translation.y, 0.3);
+                emitter_events.send(StartEmitter(particles::OVERWAIT, translation));
             }
         }
     }
 }
 
+// Waiting workers stand on an Archimedean spiral around the shop in serving order,
+// with consecutive slots and turns QUEUE_SPACING apart, wrapping inside the hex
+fn line_up(
+    time: Res<Time>,
+    speed: Res<GameSpeed>,
+    mut query: Query<(Entity, &Worker, &WaitingWorker, &mut Transform), Without<ReturningWorker>>,
+) {
+    let mut queues: HashMap<Coordinate, Vec<(ServeOrder, Entity)>> = HashMap::default();
+    for (entity, worker, waiting, _) in query.iter_mut() {
+        queues
+            .entry(worker.coffee)
+            .or_default()
+            .push((serve_order(worker, waiting), entity));
+    }
+    let step = (QUEUE_SHUFFLE_SPEED * time_k(&time) * speed.multiplier() as f32).min(1.);
+    let turn = QUEUE_SPACING / (2. * PI);
+    for (shop, mut queue) in queues {
+        queue.sort_by_key(|(order, _)| *order);
+        let (x, y) = shop.to_pixel(Spacing::FlatTop(SIZE));
+        let mut angle = 0_f32;
+        let mut lap = 0;
+        for (_, entity) in queue {
+            let mut radius = QUEUE_START_RADIUS + turn * angle;
+            // a full spiral starts over, shifted to stand between the slots of the previous lap
+            if radius > QUEUE_MAX_RADIUS {
+                lap += 1;
+                angle = (lap as f32 * QUEUE_SPACING / 2. / QUEUE_START_RADIUS) % (2. * PI);
+                radius = QUEUE_START_RADIUS + turn * angle;
+            }
+            let slot = Vec2::new(x + radius * angle.cos(), y + radius * angle.sin());
+            angle += QUEUE_SPACING / radius;
+            let (_, _, _, mut transform) = query.get_mut(entity).unwrap();
+            let pos = transform.translation.truncate();
+            transform.translation = pos.lerp(slot, step).extend(transform.translation.z);
+            let dx = x - pos.x;
+            if dx.abs() > 1. {
+                transform.scale.x = transform.scale.x.abs() * dx.signum();
+            }
+        }
+    }
+}
+
+// Paths are computed once, so workers on their way have to catch up with the map:
+// pick a closer shop, walk around new obstacles or go home when no shop is left
+fn reroute_workers(
+    mut commands: Commands,
+    map: Res<Map>,
+    kinds: Res<WorkerKinds>,
+    reputation: Res<Reputation>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Worker,
+            &Transform,
+            Option<&MovingWorker>,
+            Option<&WaitingWorker>,
+        ),
+        Without<ReturningWorker>,
+    >,
+) {
+    if !map.is_changed() {
+        return;
+    }
+    for (entity, mut worker, transform, moving, waiting) in query.iter_mut() {
+        // a moving worker is already committed to the tile it is walking to,
+        // a queued one can stand over a neighbouring tile but belongs to its shop
+        let here = if waiting.is_some() {
+            worker.coffee
+        } else {
+            moving.map(|m| m.target).unwrap_or_else(|| {
+                let t = transform.translation;
+                Coordinate::from_pixel(t.x, t.y, Spacing::FlatTop(SIZE))
+            })
+        };
+        let home = worker.home;
+        if worker.waited_for_coffee {
+            if let Some(path) = map.find_path(here, |c| c == home) {
+                worker.path = path;
+            }
+            continue;
+        }
+        if let Some(path) = map.find_path(here, |c| map.is_coffee(c)) {
+            let coffee = path.first().copied().unwrap_or(here);
+            if coffee != worker.coffee {
+                log::debug!("Rerouting worker from {:?} to {:?}", worker.coffee, coffee);
+                worker.coffee = coffee;
+                // payouts follow the distance from the office, as when spawning
+                let len = map
+                    .find_path(home, |c| c == coffee)
+                    .map(|p| p.len())
+                    .unwrap_or_else(|| path.len());
+                worker.will_bring_money = kinds.payout(worker.kind, len, &reputation);
+                commands.entity(entity).remove::<WaitingWorker>();
+            }
+            worker.path = path;
+        } else {
+            log::debug!("No shop left for a worker, going home");
+            worker.path = map.find_path(here, |c| c == home).unwrap_or_default();
+            worker.waited_for_coffee = true;
+            commands.entity(entity).remove::<WaitingWorker>();
+        }
+    }
+}
+
 pub struct SpawnWorkerEvent(pub Coordinate, pub Coordinate, pub Vec<Coordinate>);
 
 fn spawn_worker(
     mut commands: Commands,
-    atlas: Res<WorkerAtlasResource>,
+    sprites: Res<WorkerSprites>,
+    kinds: Res<WorkerKinds>,
+    difficulty: Res<DifficultySettings>,
+    reputation: Res<Reputation>,
     mut events: EventReader<SpawnWorkerEvent>,
 ) {
     for SpawnWorkerEvent(home, coffee, path) in events.iter() {
         let (x, y) = random_pos(home);
         let mut rng = thread_rng();
-        let r = rng.gen_range(0..150);
-        let g = rng.gen_range(0..150);
-        let b = rng.gen_range(0..150);
+        let kind = kinds.pick(&mut rng);
+        let params = &kinds.params[&kind];
         let head = TextureAtlasSprite {
-            color: Color::rgb_u8(r, g, b),
-            index: 0,
+            color: kind.head_color(&mut rng),
+            index: sprites.head,
             ..Default::default()
         };
         let head = SpriteSheetBundle {
             sprite: head,
-            texture_atlas: atlas.atlas.clone(),
+            texture_atlas: sprites.atlas.clone(),
             transform: Transform::from_translation(Vec3::new(0., 13., 0.1)),
             ..Default::default()
         };
-        let r = rng.gen_range(50..200);
-        let g = rng.gen_range(50..200);
-        let b = rng.gen_range(50..200);
         let body = TextureAtlasSprite {
-            color: Color::rgb_u8(r, g, b),
-            index: 1,
+            color: kind.body_color(&mut rng),
+            index: sprites.idle_frame(),
             ..Default::default()
         };
         let body = SpriteSheetBundle {
             sprite: body,
-            texture_atlas: atlas.atlas.clone(),
+            texture_atlas: sprites.atlas.clone(),
             ..Default::default()
         };
-        let main_transform = Transform::from_xyz(x, y, 0.9);
-        let will_bring_money = money_for_path(path.len());
+        let mut main_transform = Transform::from_xyz(x, y, 0.9);
+        main_transform.scale = Vec3::splat(params.scale);
+        let will_bring_money = kinds.payout(kind, path.len(), &reputation);
         commands
             .spawn()
             .insert(Worker {
@@ -160,35 +489,32 @@ fn spawn_worker(
                 path: path.clone(),
                 waited_for_coffee: false,
                 will_bring_money,
+                kind,
+                patience: (params.patience as f32 * difficulty.patience_multiplier) as u32,
+                speed: params.speed,
             })
+            .insert(WorkerAnimation::default())
             .insert(main_transform)
             .insert(GlobalTransform::default())
             .with_children(|ec| {
                 ec.spawn_bundle(head);
-                ec.spawn_bundle(body);
+                ec.spawn_bundle(body).insert(WorkerBody);
             });
     }
 }
 
-impl FromWorld for WorkerAtlasResource {
-    fn from_world(world: &mut World) -> Self {
-        let asset_server = world.get_resource_mut::<AssetServer>().unwrap();
-        let tex_handle = asset_server.load("worker.png");
-        let atlas = TextureAtlas::from_grid(tex_handle, Vec2::new(200. / 8., 198. / 8.), 2, 1);
-        let mut atlases = world.get_resource_mut::<Assets<TextureAtlas>>().unwrap();
-        let handle = atlases.add(atlas);
-        WorkerAtlasResource { atlas: handle }
-    }
-}
-
 pub struct WorkerPlugin;
 impl Plugin for WorkerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<WorkerAtlasResource>()
+        app.init_resource::<WorkerKinds>()
             .add_system(spawn_worker.system())
             .add_system(start_moving_worker.system())
             .add_system(move_worker.system())
             .add_system(wait_worker.system().before("coffee"))
-            .add_event::<SpawnWorkerEvent>();
+            .add_system(line_up.system())
+            .add_system(reroute_workers.system())
+            .add_event::<SpawnWorkerEvent>()
+            .add_event::<OverwaitEvent>()
+            .add_event::<ServedEvent>();
     }
 }